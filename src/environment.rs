@@ -0,0 +1,59 @@
+// Variable scopes for TungLang programs
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A scope of variables. The program, every block, loop iteration and function call gets its
+/// own scope, which can read and assign the variables of the scopes around it. Functions keep
+/// the scope they were defined in, so they can still use its variables after it has ended.
+pub struct Environment {
+    variables: RefCell<HashMap<String, Value>>,
+    parent: Option<Env>,
+}
+
+pub type Env = Rc<Environment>;
+
+impl Environment {
+    /// The outermost scope, holding the program's top-level variables
+    pub fn global() -> Env {
+        Rc::new(Environment {
+            variables: RefCell::new(HashMap::new()),
+            parent: None,
+        })
+    }
+
+    /// A new scope nested inside `parent`
+    pub fn child(parent: &Env) -> Env {
+        Rc::new(Environment {
+            variables: RefCell::new(HashMap::new()),
+            parent: Some(Rc::clone(parent)),
+        })
+    }
+
+    /// Looks a variable up in this scope, then in the scopes around it
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.variables.borrow().get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.parent.as_ref()?.get(name),
+        }
+    }
+
+    /// Creates (or replaces) a variable in this scope, as var does
+    pub fn define(&self, name: String, value: Value) {
+        self.variables.borrow_mut().insert(name, value);
+    }
+
+    /// Changes the nearest existing variable called `name`. Returns false if there is none.
+    pub fn assign(&self, name: &str, value: Value) -> bool {
+        self.with_variable(name, |slot| *slot = value).is_some()
+    }
+
+    /// Runs `f` on the nearest existing variable called `name`, e.g. to change one of its items
+    pub fn with_variable<R>(&self, name: &str, f: impl FnOnce(&mut Value) -> R) -> Option<R> {
+        if let Some(slot) = self.variables.borrow_mut().get_mut(name) {
+            return Some(f(slot));
+        }
+        self.parent.as_ref()?.with_variable(name, f)
+    }
+}
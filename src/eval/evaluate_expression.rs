@@ -1,15 +1,21 @@
+use crate::environment::{Env, Environment};
+use crate::eval::std::std_format::format_value;
+use crate::eval::string_literal::{decode_fstring_text, decode_string_pair};
+use crate::interpreter::{call_value, define_target, is_truthy, iterate, make_function};
 use crate::parser::Rule;
 use crate::stdlib::StdLib;
-use crate::value::Value;
+use crate::value::{Keywords, Value};
+use indexmap::{IndexMap, IndexSet};
 use pest::iterators::{Pair, Pairs};
-use std::collections::HashMap;
 
 pub fn evaluate_expression(
-    pair: Pair<Rule>,
-    variables: &HashMap<String, Value>,
+    pair: Pair<'static, Rule>,
+    env: &Env,
     stdlib: &StdLib,
 ) -> miette::Result<Value> {
-    use crate::eval::operators::apply_operator;
+    use crate::eval::operators::{
+        apply_index, apply_operator, check_hashable, contains, get_attribute,
+    };
 
     match pair.as_rule() {
         Rule::number => {
@@ -20,13 +26,11 @@ pub fn evaluate_expression(
                 Ok(Value::Number(s.parse::<i64>().unwrap()))
             }
         }
-        Rule::string => {
-            let s: &str = pair.as_str();
-            Ok(Value::String(s[1..s.len() - 1].to_string()))
-        }
+        Rule::boolean => Ok(Value::Boolean(matches!(pair.as_str(), "true" | "True"))),
+        Rule::string => Ok(Value::String(decode_string_pair(&pair)?)),
         Rule::IDENTIFIER => {
             let name: &str = pair.as_str();
-            match variables.get(name).cloned() {
+            match lookup(name, env, stdlib) {
                 Some(val) => Ok(val),
                 None => Err(miette::miette!(
                     "Error: Variable '{}' is not defined.",
@@ -37,13 +41,9 @@ pub fn evaluate_expression(
         Rule::function_call => {
             let mut inner: Pairs<Rule> = pair.into_inner();
             let func_name: &str = inner.next().unwrap().as_str();
-            let mut args = Vec::new();
-            for p in inner {
-                args.push(evaluate_expression(p, variables, stdlib)?);
-            }
-            if let Some(func) = stdlib.get(func_name) {
-                let result = func(&args);
-                Ok(result)
+            let (args, keywords) = evaluate_arguments(func_name, inner, env, stdlib)?;
+            if let Some(callee) = lookup(func_name, env, stdlib) {
+                call_value(&callee, args, keywords, stdlib)
             } else {
                 Err(miette::miette!(
                     "Error: Function '{}' is not defined.",
@@ -51,12 +51,55 @@ pub fn evaluate_expression(
                 ))
             }
         }
-        Rule::comparison | Rule::sum | Rule::term => {
+        Rule::conditional => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let value: Pair<Rule> = inner.next().unwrap();
+            let condition: Pair<Rule> = match inner.next() {
+                Some(condition) => condition,
+                None => return evaluate_expression(value, env, stdlib),
+            };
+            let otherwise: Pair<Rule> = inner.next().unwrap();
+            // Only the branch that is chosen gets evaluated
+            if is_truthy(evaluate_expression(condition, env, stdlib)?) {
+                evaluate_expression(value, env, stdlib)
+            } else {
+                evaluate_expression(otherwise, env, stdlib)
+            }
+        }
+        Rule::comparison => {
+            // a < b < c is a < b and b < c, with b evaluated once; the chain stops at the
+            // first comparison that is false
             let mut inner: Pairs<Rule> = pair.into_inner();
-            let mut left = evaluate_expression(inner.next().unwrap(), variables, stdlib)?;
+            let mut left = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
             while let Some(op_pair) = inner.next() {
                 let op: &str = op_pair.as_str();
-                let right = evaluate_expression(inner.next().unwrap(), variables, stdlib)?;
+                let right_pair: Pair<Rule> = inner.next().unwrap();
+                let last: bool = inner.peek().is_none();
+                // x in name looks inside the variable without copying it, so checking a set
+                // or dict stays fast however big it is
+                if last && matches!(op, "in" | "!in") {
+                    let found = plain_identifier(&right_pair).and_then(|name| {
+                        env.with_variable(name, |container| contains(container, &left))
+                    });
+                    if let Some(Some(found)) = found {
+                        return Ok(Value::Boolean(found == (op == "in")));
+                    }
+                }
+                let right = evaluate_expression(right_pair, env, stdlib)?;
+                let result = apply_operator(left, right.clone(), op)?;
+                if last || !is_truthy(result.clone()) {
+                    return Ok(result);
+                }
+                left = right;
+            }
+            Ok(left)
+        }
+        Rule::bitwise_or | Rule::bitwise_xor | Rule::bitwise_and | Rule::sum | Rule::term => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let mut left = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+            while let Some(op_pair) = inner.next() {
+                let op: &str = op_pair.as_str();
+                let right = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
                 left = apply_operator(left, right, op)?;
             }
             Ok(left)
@@ -64,28 +107,247 @@ pub fn evaluate_expression(
         Rule::factor => {
             let mut inner: Pairs<Rule> = pair.into_inner();
             let first: Pair<Rule> = inner.next().unwrap();
-            evaluate_expression(first, variables, stdlib)
+            let mut value = evaluate_expression(first, env, stdlib)?;
+            for postfix in inner {
+                value = match postfix.as_rule() {
+                    Rule::call => {
+                        let name: String = match &value {
+                            Value::Function(function) => function.name().to_string(),
+                            other => other.type_name().to_string(),
+                        };
+                        let (args, keywords) =
+                            evaluate_arguments(&name, postfix.into_inner(), env, stdlib)?;
+                        call_value(&value, args, keywords, stdlib)?
+                    }
+                    Rule::index => {
+                        let index =
+                            evaluate_expression(postfix.into_inner().next().unwrap(), env, stdlib)?;
+                        apply_index(&value, &index)?
+                    }
+                    Rule::attribute => {
+                        get_attribute(&value, postfix.into_inner().next().unwrap().as_str())?
+                    }
+                    _ => {
+                        let mut call: Pairs<Rule> = postfix.into_inner();
+                        let method_name: &str = call.next().unwrap().as_str();
+                        // Instances, classes and modules have their own methods and fields
+                        if let Value::Instance(_) | Value::Class(_) | Value::Module(_) = value {
+                            let method: Value = get_attribute(&value, method_name)?;
+                            let (args, keywords) =
+                                evaluate_arguments(method_name, call, env, stdlib)?;
+                            value = call_value(&method, args, keywords, stdlib)?;
+                            continue;
+                        }
+                        let type_name: &str = value.type_name();
+                        let method = match stdlib.get_method(type_name, method_name) {
+                            Some(method) => method,
+                            None => {
+                                return Err(miette::miette!(
+                                    "Error: '{}' object has no method '{}'.",
+                                    type_name,
+                                    method_name
+                                ))
+                            }
+                        };
+                        let (mut args, keywords) =
+                            evaluate_arguments(method_name, call, env, stdlib)?;
+                        args.insert(0, value);
+                        method.call(method_name, args, keywords, stdlib)?
+                    }
+                };
+            }
+            Ok(value)
+        }
+        Rule::fstring => {
+            let mut result = String::new();
+            let mut raw: bool = false;
+            for part in pair.into_inner() {
+                match part.as_rule() {
+                    Rule::fstring_prefix => raw = part.as_str() != "f",
+                    Rule::fstring_text => result.push_str(&decode_fstring_text(&part, raw)?),
+                    Rule::fstring_field => {
+                        let mut field: Pairs<Rule> = part.into_inner();
+                        let value = evaluate_expression(field.next().unwrap(), env, stdlib)?;
+                        let spec: &str = field.next().map_or("", |spec| &spec.as_str()[1..]);
+                        match format_value(&value, spec) {
+                            Ok(text) => result.push_str(&text),
+                            Err(e) => return Err(miette::miette!("ValueError: {}.", e)),
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            Ok(Value::String(result))
         }
         Rule::array => {
             let mut elements = Vec::new();
             for p in pair.into_inner() {
-                elements.push(evaluate_expression(p, variables, stdlib)?);
+                elements.push(evaluate_expression(p, env, stdlib)?);
             }
             Ok(Value::Array(elements))
         }
+        Rule::tuple | Rule::bare_tuple => {
+            let mut elements = Vec::new();
+            for p in pair.into_inner() {
+                elements.push(evaluate_expression(p, env, stdlib)?);
+            }
+            Ok(Value::Tuple(elements))
+        }
         Rule::dict => {
-            let mut map: HashMap<String, Value> = HashMap::new();
+            let mut map: IndexMap<Value, Value> = IndexMap::new();
             for entry in pair.into_inner() {
                 let mut kv: Pairs<Rule> = entry.into_inner();
-                let k: String = kv.next().unwrap().as_str().to_string();
-                let v: Value = evaluate_expression(kv.next().unwrap(), variables, stdlib)?;
+                let k: Value = evaluate_expression(kv.next().unwrap(), env, stdlib)?;
+                check_hashable(&k)?;
+                let v: Value = evaluate_expression(kv.next().unwrap(), env, stdlib)?;
                 map.insert(k, v);
             }
             Ok(Value::Dict(map))
         }
-        Rule::expression => {
-            evaluate_expression(pair.into_inner().next().unwrap(), variables, stdlib)
+        Rule::set => {
+            let mut set: IndexSet<Value> = IndexSet::new();
+            for p in pair.into_inner() {
+                let item: Value = evaluate_expression(p, env, stdlib)?;
+                check_hashable(&item)?;
+                set.insert(item);
+            }
+            Ok(Value::Set(set))
+        }
+        Rule::list_comprehension => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let element: Pair<Rule> = inner.next().unwrap();
+            let clauses: Vec<Pair<Rule>> = inner.collect();
+            let mut elements = Vec::new();
+            run_comprehension(&clauses, env, stdlib, &mut |scope| {
+                elements.push(evaluate_expression(element.clone(), scope, stdlib)?);
+                Ok(())
+            })?;
+            Ok(Value::Array(elements))
         }
+        Rule::dict_comprehension => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let key: Pair<Rule> = inner.next().unwrap();
+            let value: Pair<Rule> = inner.next().unwrap();
+            let clauses: Vec<Pair<Rule>> = inner.collect();
+            let mut map: IndexMap<Value, Value> = IndexMap::new();
+            run_comprehension(&clauses, env, stdlib, &mut |scope| {
+                let k: Value = evaluate_expression(key.clone(), scope, stdlib)?;
+                check_hashable(&k)?;
+                let v: Value = evaluate_expression(value.clone(), scope, stdlib)?;
+                map.insert(k, v);
+                Ok(())
+            })?;
+            Ok(Value::Dict(map))
+        }
+        Rule::set_comprehension => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let element: Pair<Rule> = inner.next().unwrap();
+            let clauses: Vec<Pair<Rule>> = inner.collect();
+            let mut set: IndexSet<Value> = IndexSet::new();
+            run_comprehension(&clauses, env, stdlib, &mut |scope| {
+                let item: Value = evaluate_expression(element.clone(), scope, stdlib)?;
+                check_hashable(&item)?;
+                set.insert(item);
+                Ok(())
+            })?;
+            Ok(Value::Set(set))
+        }
+        Rule::lambda => {
+            let body: Pairs<Rule> = pair.into_inner().next().unwrap().into_inner();
+            make_function("<lambda>".to_string(), body, env, stdlib)
+        }
+        Rule::function_expression => {
+            make_function("<lambda>".to_string(), pair.into_inner(), env, stdlib)
+        }
+        Rule::expression => evaluate_expression(pair.into_inner().next().unwrap(), env, stdlib),
         _ => Err(miette::miette!("Error: Invalid expression.")),
     }
 }
+
+/// Runs the for and if clauses of a comprehension, calling `emit` with the scope of every
+/// combination that passes the filters. Each loop item gets a scope of its own, so loop
+/// variables don't leak out of the comprehension.
+fn run_comprehension(
+    clauses: &[Pair<'static, Rule>],
+    env: &Env,
+    stdlib: &StdLib,
+    emit: &mut dyn FnMut(&Env) -> miette::Result<()>,
+) -> miette::Result<()> {
+    let (clause, rest) = match clauses.split_first() {
+        Some(split) => split,
+        None => return emit(env),
+    };
+    let mut inner: Pairs<Rule> = clause.clone().into_inner();
+    if clause.as_rule() == Rule::comprehension_if {
+        let condition: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+        if is_truthy(condition) {
+            run_comprehension(rest, env, stdlib, emit)?;
+        }
+        return Ok(());
+    }
+    let target: Pair<Rule> = inner.next().unwrap();
+    let iterable: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+    for item in iterate(iterable)? {
+        let scope: Env = Environment::child(env);
+        define_target(target.clone(), item, &scope)?;
+        run_comprehension(rest, &scope, stdlib, emit)?;
+    }
+    Ok(())
+}
+
+/// The variable name an operand consists of, if it is nothing more than a name
+fn plain_identifier(pair: &Pair<'static, Rule>) -> Option<&'static str> {
+    let mut pair: Pair<Rule> = pair.clone();
+    loop {
+        if pair.as_rule() == Rule::IDENTIFIER {
+            return Some(pair.as_str());
+        }
+        let mut inner: Pairs<Rule> = pair.into_inner();
+        pair = inner.next()?;
+        if inner.next().is_some() {
+            return None;
+        }
+    }
+}
+
+/// Finds a variable, or else a builtin function of that name, e.g. var measure = len
+fn lookup(name: &str, env: &Env, stdlib: &StdLib) -> Option<Value> {
+    env.get(name)
+        .or_else(|| stdlib.lookup(name).map(Value::Function))
+}
+
+/// Evaluates the arguments of a call to `name`, splitting them into positional values and
+/// keyword arguments
+pub fn evaluate_arguments(
+    name: &str,
+    arguments: Pairs<'static, Rule>,
+    env: &Env,
+    stdlib: &StdLib,
+) -> miette::Result<(Vec<Value>, Keywords)> {
+    let mut args: Vec<Value> = Vec::new();
+    let mut keywords: Keywords = Keywords::new();
+    for argument in arguments {
+        if argument.as_rule() != Rule::keyword_argument {
+            if !keywords.is_empty() {
+                return Err(miette::miette!(
+                    "Error: Positional argument follows keyword argument in {}().",
+                    name
+                ));
+            }
+            args.push(evaluate_expression(argument, env, stdlib)?);
+            continue;
+        }
+        let mut inner: Pairs<Rule> = argument.into_inner();
+        let keyword: String = inner.next().unwrap().as_str().to_string();
+        let value: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+        if keywords.contains_key(&keyword) {
+            return Err(miette::miette!(
+                "Error: {}() got multiple values for keyword argument '{}'.",
+                name,
+                keyword
+            ));
+        }
+        keywords.insert(keyword, value);
+    }
+    Ok((args, keywords))
+}
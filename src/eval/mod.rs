@@ -1,4 +1,6 @@
 // TungLang evaluation logic module
 pub mod evaluate_expression;
 pub mod operators;
+pub mod pattern;
 pub mod std;
+pub mod string_literal;
@@ -1,9 +1,17 @@
 // Handles arithmetic and logical operators for TungLang
-use crate::value::Value;
+use crate::value::{duration_parts, Function, Value};
+use chrono::{Datelike, NaiveDate, TimeDelta, Timelike};
 use miette::Result;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
+use std::rc::Rc;
 
 /// Applies a binary operator to two Values
 pub fn apply_operator(left: Value, right: Value, op: &str) -> Result<Value> {
+    if let ("in" | "!in", Some(found)) = (op, contains(&right, &left)) {
+        return Ok(Value::Boolean(found == (op == "in")));
+    }
     match (left.clone(), right.clone(), op) {
         // Arithmetic - Python-like behavior with auto-promotion to float
         (Value::Number(l), Value::Number(r), "+") => Ok(Value::Number(l + r)),
@@ -42,23 +50,30 @@ pub fn apply_operator(left: Value, right: Value, op: &str) -> Result<Value> {
         (Value::Float(l), Value::Float(r), "//") => Ok(Value::Number((l / r).floor() as i64)),
         (Value::Float(l), Value::Float(r), "%") => Ok(Value::Float(l % r)),
         (Value::Float(l), Value::Float(r), "**") => Ok(Value::Float(l.powf(r))),
+
+        // Decimal arithmetic stays exact: integers are promoted to decimal, but
+        // floats are rejected instead of quietly reintroducing binary rounding
+        (Value::Decimal(l), Value::Decimal(r), op) if is_arithmetic(op) => {
+            decimal_arithmetic(l, r, op)
+        }
+        (Value::Decimal(l), Value::Number(r), op) if is_arithmetic(op) => {
+            decimal_arithmetic(l, Decimal::from(r), op)
+        }
+        (Value::Number(l), Value::Decimal(r), op) if is_arithmetic(op) => {
+            decimal_arithmetic(Decimal::from(l), r, op)
+        }
+        (Value::Decimal(_), Value::Float(_), op) | (Value::Float(_), Value::Decimal(_), op)
+            if is_arithmetic(op) =>
+        {
+            Err(miette::miette!(
+                "Error: Unsupported operation '{}' between decimal and float. Convert the float with decimal() first.",
+                op
+            ))
+        }
         // String concatenation and Python-like string operations
         (Value::String(l), Value::String(r), "+") => Ok(Value::String(l + &r)),
-        (Value::String(l), Value::Number(r), "+") => Ok(Value::String(l + &r.to_string())),
-        (Value::String(l), Value::Float(r), "+") => Ok(Value::String(l + &r.to_string())),
-        (Value::String(l), Value::Boolean(r), "+") => Ok(Value::String(l + &r.to_string())),
-        (Value::String(l), Value::Array(r), "+") => Ok(Value::String(l + &format!("{:?}", r))),
-        (Value::String(l), Value::Dict(r), "+") => Ok(Value::String(l + &format!("{:?}", r))),
-        (Value::String(l), Value::Undefined, "+") => Ok(Value::String(l + "undefined")),
-
-        (Value::Number(l), Value::String(r), "+") => Ok(Value::String(l.to_string() + &r)),
-        (Value::Float(l), Value::String(r), "+") => Ok(Value::String(l.to_string() + &r)),
-        (Value::Boolean(l), Value::String(r), "+") => Ok(Value::String(l.to_string() + &r)),
-        (Value::Array(l), Value::String(r), "+") => Ok(Value::String(format!("{:?}", l) + &r)),
-        (Value::Dict(l), Value::String(r), "+") => Ok(Value::String(format!("{:?}", l) + &r)),
-        (Value::Undefined, Value::String(r), "+") => {
-            Ok(Value::String("undefined".to_string() + &r))
-        }
+        (Value::String(l), r, "+") => Ok(Value::String(l + &r.to_string())),
+        (l, Value::String(r), "+") => Ok(Value::String(l.to_string() + &r)),
 
         // Python-like string repetition with * operator
         (Value::String(s), Value::Number(n), "*") => {
@@ -75,6 +90,79 @@ pub fn apply_operator(left: Value, right: Value, op: &str) -> Result<Value> {
                 Ok(Value::String(s.repeat(n as usize)))
             }
         }
+        // Dates and times move by durations, and subtracting one from another gives the
+        // duration between them. Dates only move by whole days.
+        (Value::Date(d), Value::Duration(t), "+") | (Value::Duration(t), Value::Date(d), "+") => {
+            in_range(d.checked_add_signed(t).map(Value::Date))
+        }
+        (Value::Date(d), Value::Duration(t), "-") => {
+            in_range(d.checked_sub_signed(t).map(Value::Date))
+        }
+        (Value::DateTime(d), Value::Duration(t), "+")
+        | (Value::Duration(t), Value::DateTime(d), "+") => {
+            in_range(d.checked_add_signed(t).map(Value::DateTime))
+        }
+        (Value::DateTime(d), Value::Duration(t), "-") => {
+            in_range(d.checked_sub_signed(t).map(Value::DateTime))
+        }
+        (Value::Date(l), Value::Date(r), "-") => Ok(Value::Duration(l - r)),
+        (Value::DateTime(l), Value::DateTime(r), "-") => Ok(Value::Duration(l - r)),
+        (Value::Duration(l), Value::Duration(r), "+") => {
+            in_range(l.checked_add(&r).map(Value::Duration))
+        }
+        (Value::Duration(l), Value::Duration(r), "-") => {
+            in_range(l.checked_sub(&r).map(Value::Duration))
+        }
+        (Value::Duration(t), Value::Number(n), "*") | (Value::Number(n), Value::Duration(t), "*") => {
+            scale_duration(t, n as f64)
+        }
+        (Value::Duration(t), Value::Float(f), "*") | (Value::Float(f), Value::Duration(t), "*") => {
+            scale_duration(t, f)
+        }
+        (Value::Duration(t), Value::Number(n), "/") => divide_duration(t, n as f64),
+        (Value::Duration(t), Value::Float(f), "/") => divide_duration(t, f),
+        (Value::Duration(l), Value::Duration(r), "/") => {
+            if r.is_zero() {
+                return Err(miette::miette!("ZeroDivisionError: duration division by zero."));
+            }
+            Ok(Value::Float(l.as_seconds_f64() / r.as_seconds_f64()))
+        }
+        (Value::Date(l), Value::Date(r), op) if is_ordering(op) => {
+            Ok(Value::Boolean(compare(l, r, op)))
+        }
+        (Value::DateTime(l), Value::DateTime(r), op) if is_ordering(op) => {
+            Ok(Value::Boolean(compare(l, r, op)))
+        }
+        (Value::Duration(l), Value::Duration(r), op) if is_ordering(op) => {
+            Ok(Value::Boolean(compare(l, r, op)))
+        }
+        // Set algebra: union, intersection, difference and symmetric difference, keeping
+        // the left set's order
+        (Value::Set(l), Value::Set(r), "|") => Ok(Value::Set(l.union(&r).cloned().collect())),
+        (Value::Set(l), Value::Set(r), "&") => {
+            Ok(Value::Set(l.intersection(&r).cloned().collect()))
+        }
+        (Value::Set(l), Value::Set(r), "-") => Ok(Value::Set(l.difference(&r).cloned().collect())),
+        (Value::Set(l), Value::Set(r), "^") => {
+            Ok(Value::Set(l.symmetric_difference(&r).cloned().collect()))
+        }
+        // Bitwise operators on integers
+        (Value::Number(l), Value::Number(r), "|") => Ok(Value::Number(l | r)),
+        (Value::Number(l), Value::Number(r), "&") => Ok(Value::Number(l & r)),
+        (Value::Number(l), Value::Number(r), "^") => Ok(Value::Number(l ^ r)),
+        // Tuple concatenation and repetition, giving new tuples
+        (Value::Tuple(mut l), Value::Tuple(r), "+") => {
+            l.extend(r);
+            Ok(Value::Tuple(l))
+        }
+        (Value::Tuple(t), Value::Number(n), "*") | (Value::Number(n), Value::Tuple(t), "*") => {
+            Ok(Value::Tuple((0..n).flat_map(|_| t.clone()).collect()))
+        }
+        // Tuples order item by item, like sorting does
+        (Value::Tuple(_), Value::Tuple(_), op) if is_ordering(op) => {
+            let ordering = compare_values(&left, &right)?;
+            Ok(Value::Boolean(compare(ordering, Ordering::Equal, op)))
+        }
         // Array concatenation and other Python-like array operations
         (Value::Array(mut l), Value::Array(r), "+") => {
             l.extend(r);
@@ -160,6 +248,7 @@ pub fn apply_operator(left: Value, right: Value, op: &str) -> Result<Value> {
         (Value::Boolean(l), Value::Undefined, "!") => Ok(Value::Boolean(!l)),
         (Value::Number(l), Value::Undefined, "-") => Ok(Value::Number(-l)),
         (Value::Float(l), Value::Undefined, "-") => Ok(Value::Float(-l)),
+        (Value::Decimal(l), Value::Undefined, "-") => Ok(Value::Decimal(-l)),
         // Type conversion for comparison (Python allows comparing different numeric types)
         (Value::Number(l), Value::Float(r), op)
             if matches!(op, "==" | "!=" | ">" | "<" | ">=" | "<=") =>
@@ -191,25 +280,392 @@ pub fn apply_operator(left: Value, right: Value, op: &str) -> Result<Value> {
             };
             Ok(Value::Boolean(result))
         }
-
-        // Python-like 'in' operator for arrays and dicts
-        (item, Value::Array(arr), "in") => Ok(Value::Boolean(arr.contains(&item))),
-        (Value::String(key), Value::Dict(dict), "in") => {
-            Ok(Value::Boolean(dict.contains_key(&key)))
+        // Decimals compare exactly with decimals and integers; ordering against a
+        // float goes through the float value, equality stays exact (see Value's PartialEq)
+        (Value::Decimal(l), Value::Decimal(r), op) if is_comparison(op) => {
+            Ok(Value::Boolean(compare(l, r, op)))
         }
-
-        // Python-like 'not in' operator for arrays and dicts
-        (item, Value::Array(arr), "!in") => Ok(Value::Boolean(!arr.contains(&item))),
-        (Value::String(key), Value::Dict(dict), "!in") => {
-            Ok(Value::Boolean(!dict.contains_key(&key)))
+        (Value::Decimal(l), Value::Number(r), op) if is_comparison(op) => {
+            Ok(Value::Boolean(compare(l, Decimal::from(r), op)))
         }
+        (Value::Number(l), Value::Decimal(r), op) if is_comparison(op) => {
+            Ok(Value::Boolean(compare(Decimal::from(l), r, op)))
+        }
+        (Value::Decimal(l), Value::Float(r), op) if is_ordering(op) => {
+            Ok(Value::Boolean(compare(l.to_f64().unwrap_or(f64::NAN), r, op)))
+        }
+        (Value::Float(l), Value::Decimal(r), op) if is_ordering(op) => {
+            Ok(Value::Boolean(compare(l, r.to_f64().unwrap_or(f64::NAN), op)))
+        }
+
+        // Equality between any other values, e.g. lists, dicts or mismatched types
+        (l, r, "==") => Ok(Value::Boolean(l == r)),
+        (l, r, "!=") => Ok(Value::Boolean(l != r)),
 
         // Fall through cases
         _ => Err(miette::miette!(
-            "Error: Unsupported operation '{}' between types {:?} and {:?}",
+            "Error: Unsupported operation '{}' between types {} and {}",
             op,
-            left,
-            right
+            left.type_name(),
+            right.type_name()
+        )),
+    }
+}
+
+/// Python-like 'in' for arrays, tuples, sets and dicts, or None for other containers. The
+/// container isn't copied, so sets and dicts find the item by hash in constant time.
+pub fn contains(container: &Value, item: &Value) -> Option<bool> {
+    match container {
+        Value::Array(items) | Value::Tuple(items) => Some(items.contains(item)),
+        Value::Set(set) => Some(set.contains(item)),
+        Value::Dict(map) => Some(map.contains_key(item)),
+        _ => None,
+    }
+}
+
+/// Orders two values the way sorting does: numbers by value, strings alphabetically, and
+/// lists and tuples item by item. Values of types that can't be ordered against each other give
+/// a TypeError.
+pub fn compare_values(left: &Value, right: &Value) -> Result<Ordering> {
+    match (left, right) {
+        (Value::Array(l), Value::Array(r)) | (Value::Tuple(l), Value::Tuple(r)) => {
+            for (a, b) in l.iter().zip(r) {
+                let ordering = compare_values(a, b)?;
+                if ordering != Ordering::Equal {
+                    return Ok(ordering);
+                }
+            }
+            Ok(l.len().cmp(&r.len()))
+        }
+        (Value::Boolean(l), Value::Boolean(r)) => Ok(l.cmp(r)),
+        _ => {
+            let less = |l: &Value, r: &Value| match apply_operator(l.clone(), r.clone(), "<") {
+                Ok(Value::Boolean(result)) => Ok(result),
+                _ => Err(miette::miette!(
+                    "TypeError: '<' not supported between instances of '{}' and '{}'.",
+                    left.type_name(),
+                    right.type_name()
+                )),
+            };
+            if less(left, right)? {
+                Ok(Ordering::Less)
+            } else if less(right, left)? {
+                Ok(Ordering::Greater)
+            } else {
+                Ok(Ordering::Equal)
+            }
+        }
+    }
+}
+
+fn in_range(result: Option<Value>) -> Result<Value> {
+    result.ok_or_else(|| miette::miette!("OverflowError: date value out of range."))
+}
+
+/// Multiplies a duration by a number, to the nearest microsecond
+fn scale_duration(duration: TimeDelta, factor: f64) -> Result<Value> {
+    let micros: f64 = (duration.num_microseconds().unwrap_or(i64::MAX) as f64 * factor).round();
+    // i64::MAX as f64 rounds up to 2^63, which is already out of range
+    if !micros.is_finite() || micros.abs() >= i64::MAX as f64 {
+        return Err(miette::miette!("OverflowError: date value out of range."));
+    }
+    Ok(Value::Duration(TimeDelta::microseconds(micros as i64)))
+}
+
+fn divide_duration(duration: TimeDelta, divisor: f64) -> Result<Value> {
+    if divisor == 0.0 {
+        return Err(miette::miette!(
+            "ZeroDivisionError: duration division by zero."
+        ));
+    }
+    scale_duration(duration, 1.0 / divisor)
+}
+
+fn is_arithmetic(op: &str) -> bool {
+    matches!(op, "+" | "-" | "*" | "/" | "//" | "%" | "**")
+}
+
+fn is_comparison(op: &str) -> bool {
+    matches!(op, "==" | "!=" | ">" | "<" | ">=" | "<=")
+}
+
+fn is_ordering(op: &str) -> bool {
+    matches!(op, ">" | "<" | ">=" | "<=")
+}
+
+fn compare<T: PartialOrd>(l: T, r: T, op: &str) -> bool {
+    match op {
+        "==" => l == r,
+        "!=" => l != r,
+        ">" => l > r,
+        "<" => l < r,
+        ">=" => l >= r,
+        "<=" => l <= r,
+        _ => unreachable!(),
+    }
+}
+
+/// Exact decimal arithmetic, reporting overflow and division by zero as errors
+fn decimal_arithmetic(l: Decimal, r: Decimal, op: &str) -> Result<Value> {
+    if matches!(op, "/" | "//" | "%") && r.is_zero() {
+        return Err(miette::miette!("Error: Decimal division by zero."));
+    }
+    let result = match op {
+        "+" => l.checked_add(r),
+        "-" => l.checked_sub(r),
+        "*" => l.checked_mul(r),
+        "/" => l.checked_div(r),
+        "//" => l.checked_div(r).map(|q| q.floor()),
+        "%" => l.checked_rem(r),
+        "**" => {
+            let exponent = r.to_i64().filter(|_| r.is_integer()).ok_or_else(|| {
+                miette::miette!("Error: Decimal exponent must be a whole number, got {}.", r)
+            })?;
+            decimal_pow(l, exponent)
+        }
+        _ => unreachable!(),
+    };
+    result
+        .map(Value::Decimal)
+        .ok_or_else(|| miette::miette!("Error: Decimal overflow in '{}' operation.", op))
+}
+
+fn decimal_pow(base: Decimal, exponent: i64) -> Option<Decimal> {
+    // Exponentiation by squaring
+    let mut result = Decimal::ONE;
+    let mut square = base;
+    let mut remaining = exponent.unsigned_abs();
+    while remaining > 0 {
+        if remaining & 1 == 1 {
+            result = result.checked_mul(square)?;
+        }
+        remaining >>= 1;
+        if remaining > 0 {
+            square = square.checked_mul(square)?;
+        }
+    }
+    if exponent < 0 {
+        Decimal::ONE.checked_div(result)
+    } else {
+        Some(result)
+    }
+}
+
+/// Looks up `container[index]` for lists, tuples, strings and dicts
+pub fn apply_index(container: &Value, index: &Value) -> Result<Value> {
+    match (container, index) {
+        (Value::Array(arr), Value::Number(i)) => match resolve_index(*i, arr.len()) {
+            Some(i) => Ok(arr[i].clone()),
+            None => Err(miette::miette!("Error: List index {} out of range.", i)),
+        },
+        (Value::Tuple(items), Value::Number(i)) => match resolve_index(*i, items.len()) {
+            Some(i) => Ok(items[i].clone()),
+            None => Err(miette::miette!("Error: Tuple index {} out of range.", i)),
+        },
+        (Value::String(s), Value::Number(i)) => {
+            let len = s.chars().count();
+            match resolve_index(*i, len) {
+                Some(i) => Ok(Value::String(s.chars().nth(i).unwrap().to_string())),
+                None => Err(miette::miette!("Error: String index {} out of range.", i)),
+            }
+        }
+        (Value::Dict(map), key) => match map.get(key) {
+            Some(value) => Ok(value.clone()),
+            None => Err(miette::miette!("Error: Key {} not found in dict.", key)),
+        },
+        _ => Err(miette::miette!(
+            "Error: Cannot index {} with {}.",
+            container.type_name(),
+            index.type_name()
+        )),
+    }
+}
+
+/// Performs `container[index] = value` in place
+pub fn assign_index(container: &mut Value, index: Value, value: Value) -> Result<()> {
+    match (container, index) {
+        (Value::Array(arr), Value::Number(i)) => match resolve_index(i, arr.len()) {
+            Some(i) => {
+                arr[i] = value;
+                Ok(())
+            }
+            None => Err(miette::miette!(
+                "Error: List assignment index {} out of range.",
+                i
+            )),
+        },
+        (Value::Dict(map), key) => {
+            check_hashable(&key)?;
+            map.insert(key, value);
+            Ok(())
+        }
+        (Value::Tuple(_), _) => Err(miette::miette!(
+            "TypeError: 'tuple' object does not support item assignment."
         )),
+        (container, index) => Err(miette::miette!(
+            "Error: Cannot assign to an item of {} with {}.",
+            container.type_name(),
+            index.type_name()
+        )),
+    }
+}
+
+/// Performs `del container[index]` in place, keeping the order of what is left
+pub fn delete_index(container: &mut Value, index: &Value) -> Result<()> {
+    match (container, index) {
+        (Value::Array(arr), Value::Number(i)) => match resolve_index(*i, arr.len()) {
+            Some(i) => {
+                arr.remove(i);
+                Ok(())
+            }
+            None => Err(miette::miette!(
+                "Error: List deletion index {} out of range.",
+                i
+            )),
+        },
+        (Value::Dict(map), key) => match map.shift_remove(key) {
+            Some(_) => Ok(()),
+            None => Err(miette::miette!("Error: Key {} not found in dict.", key)),
+        },
+        (Value::Tuple(_), _) => Err(miette::miette!(
+            "TypeError: 'tuple' object does not support item deletion."
+        )),
+        (container, index) => Err(miette::miette!(
+            "Error: Cannot delete an item of {} with {}.",
+            container.type_name(),
+            index.type_name()
+        )),
+    }
+}
+
+/// Looks up `value.name`: an instance's field, or else a method of its class bound to it. On a
+/// class it gives the method itself, so a subclass can call Parent.init(self, ...). Enums have
+/// their variants as attributes, and each variant has a name and a value. A module's attributes
+/// are its top-level variables. Dates have their year, month and day (and datetimes their hour,
+/// minute, second and microsecond), durations their days, seconds and microseconds, and files
+/// their name, mode and whether they are closed.
+pub fn get_attribute(value: &Value, name: &str) -> Result<Value> {
+    let found = match value {
+        Value::Instance(instance) => instance.fields.borrow().get(name).cloned().or_else(|| {
+            let method = instance.class.find_method(name)?;
+            Some(Value::Function(Function::Bound(
+                Rc::clone(instance),
+                method,
+            )))
+        }),
+        Value::Class(class) => class
+            .find_method(name)
+            .map(|method| Value::Function(Function::User(method))),
+        Value::Enum(enum_type) => enum_type
+            .variants
+            .iter()
+            .position(|(variant, _)| *variant == name)
+            .map(|index| Value::Variant(Rc::clone(enum_type), index)),
+        Value::Variant(enum_type, index) => match name {
+            "name" => Some(Value::String(enum_type.variants[*index].0.to_string())),
+            "value" => Some(enum_type.variants[*index].1.clone()),
+            _ => None,
+        },
+        Value::Module(module) => module.globals.get(name),
+        Value::Date(date) => date_part(date, name),
+        Value::DateTime(datetime) => match name {
+            "hour" => Some(Value::Number(datetime.hour() as i64)),
+            "minute" => Some(Value::Number(datetime.minute() as i64)),
+            "second" => Some(Value::Number(datetime.second() as i64)),
+            "microsecond" => Some(Value::Number((datetime.nanosecond() / 1000) as i64)),
+            _ => date_part(&datetime.date(), name),
+        },
+        Value::File(file) => match name {
+            "name" => Some(Value::String(file.name.clone())),
+            "mode" => Some(Value::String(file.mode.clone())),
+            "closed" => Some(Value::Boolean(file.is_closed())),
+            _ => None,
+        },
+        Value::Duration(duration) => {
+            let (days, seconds, micros) = duration_parts(duration);
+            match name {
+                "days" => Some(Value::Number(days)),
+                "seconds" => Some(Value::Number(seconds)),
+                "microseconds" => Some(Value::Number(micros)),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    found.ok_or_else(|| no_attribute(value, name))
+}
+
+fn date_part(date: &NaiveDate, name: &str) -> Option<Value> {
+    match name {
+        "year" => Some(Value::Number(date.year() as i64)),
+        "month" => Some(Value::Number(date.month() as i64)),
+        "day" => Some(Value::Number(date.day() as i64)),
+        _ => None,
+    }
+}
+
+/// Performs `value.name = new_value`, adding the field if the instance (or the variable if the
+/// module) doesn't have it yet
+pub fn set_attribute(value: &Value, name: &str, new_value: Value) -> Result<()> {
+    match value {
+        Value::Instance(instance) => {
+            instance
+                .fields
+                .borrow_mut()
+                .insert(name.to_string(), new_value);
+            Ok(())
+        }
+        Value::Module(module) => {
+            module.globals.define(name.to_string(), new_value);
+            Ok(())
+        }
+        _ => Err(no_attribute(value, name)),
+    }
+}
+
+pub fn no_attribute(value: &Value, name: &str) -> miette::Report {
+    match value {
+        Value::Class(class) => miette::miette!(
+            "AttributeError: type object '{}' has no attribute '{}'.",
+            class.name,
+            name
+        ),
+        Value::Enum(enum_type) => miette::miette!(
+            "AttributeError: type object '{}' has no attribute '{}'.",
+            enum_type.name,
+            name
+        ),
+        Value::Module(module) => miette::miette!(
+            "AttributeError: module '{}' has no attribute '{}'.",
+            module.name,
+            name
+        ),
+        _ => miette::miette!(
+            "AttributeError: '{}' object has no attribute '{}'.",
+            value.type_name(),
+            name
+        ),
+    }
+}
+
+/// Rejects values that can't be used as dict keys or set items
+pub fn check_hashable(key: &Value) -> Result<()> {
+    if key.is_hashable() {
+        Ok(())
+    } else {
+        Err(miette::miette!(
+            "Error: Unhashable type '{}' can't be used as a dict key or set item.",
+            key.type_name()
+        ))
+    }
+}
+
+// Turns a possibly negative Python-style index into a position within len
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    let resolved = if index < 0 { len as i64 + index } else { index };
+    if resolved >= 0 && (resolved as usize) < len {
+        Some(resolved as usize)
+    } else {
+        None
     }
 }
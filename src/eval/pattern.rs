@@ -0,0 +1,165 @@
+// Structural pattern matching for TungLang match statements
+use crate::environment::Env;
+use crate::eval::evaluate_expression::evaluate_expression;
+use crate::eval::operators::get_attribute;
+use crate::parser::Rule;
+use crate::stdlib::StdLib;
+use crate::value::{Enum, Value};
+use pest::iterators::Pair;
+use std::rc::Rc;
+
+/// Names a pattern binds, in the order they appear
+pub type Bindings = Vec<(String, Value)>;
+
+/// Checks `value` against a case pattern, collecting the names it binds. A pattern can be a
+/// literal, a dotted name such as `Currency.GBP`, a capture name, the wildcard `_`, a sequence such as `[x, *rest]` or a mapping
+/// such as `{"k": v}`, with patterns nested inside the last two.
+pub fn match_pattern(
+    pattern: Pair<'static, Rule>,
+    value: &Value,
+    bindings: &mut Bindings,
+    env: &Env,
+    stdlib: &StdLib,
+) -> miette::Result<bool> {
+    match pattern.as_rule() {
+        Rule::wildcard_pattern => Ok(true),
+        Rule::capture_pattern => {
+            bind(pattern.as_str(), value.clone(), bindings)?;
+            Ok(true)
+        }
+        Rule::literal_pattern => {
+            let literal = evaluate_expression(pattern.into_inner().next().unwrap(), env, stdlib)?;
+            Ok(*value == literal)
+        }
+        Rule::value_pattern => Ok(*value == evaluate_value_pattern(pattern, env, stdlib)?),
+        Rule::sequence_pattern => {
+            // Like Python, strings aren't matched as sequences of characters
+            let items: &[Value] = match value {
+                Value::Array(items) | Value::Tuple(items) => items,
+                _ => return Ok(false),
+            };
+            let patterns: Vec<Pair<Rule>> = pattern.into_inner().collect();
+            let star: Option<usize> = patterns
+                .iter()
+                .position(|p| p.as_rule() == Rule::star_pattern);
+            let fixed: usize = patterns.len() - star.is_some() as usize;
+            if items.len() < fixed || (star.is_none() && items.len() != fixed) {
+                return Ok(false);
+            }
+            // The starred name takes the items between the patterns before and after it
+            let rest: usize = items.len() - fixed;
+            let mut position: usize = 0;
+            for p in patterns {
+                if p.as_rule() == Rule::star_pattern {
+                    let name: &str = p.into_inner().next().unwrap().as_str();
+                    let taken: Vec<Value> = items[position..position + rest].to_vec();
+                    if name != "_" {
+                        bind(name, Value::Array(taken), bindings)?;
+                    }
+                    position += rest;
+                } else {
+                    if !match_pattern(p, &items[position], bindings, env, stdlib)? {
+                        return Ok(false);
+                    }
+                    position += 1;
+                }
+            }
+            Ok(true)
+        }
+        Rule::mapping_pattern => {
+            // Every key in the pattern must be there; other keys are ignored
+            let map = match value {
+                Value::Dict(map) => map,
+                _ => return Ok(false),
+            };
+            for entry in pattern.into_inner() {
+                let mut inner = entry.into_inner();
+                let key_pattern: Pair<Rule> = inner.next().unwrap();
+                let key =
+                    evaluate_expression(key_pattern.into_inner().next().unwrap(), env, stdlib)?;
+                match map.get(&key) {
+                    Some(item) => {
+                        if !match_pattern(inner.next().unwrap(), item, bindings, env, stdlib)? {
+                            return Ok(false);
+                        }
+                    }
+                    None => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Checks that a match on a variant of `enum_type` handles every variant, either with a
+/// `case Currency.GBP =>` for each one or with a `_` or capture case. Cases with a guard
+/// don't count, since the guard might fail.
+pub fn check_exhaustive(
+    enum_type: &Rc<Enum>,
+    cases: &[Pair<'static, Rule>],
+    env: &Env,
+    stdlib: &StdLib,
+) -> miette::Result<()> {
+    let mut covered: Vec<bool> = vec![false; enum_type.variants.len()];
+    for case in cases {
+        let mut parts = case.clone().into_inner();
+        let pattern: Pair<Rule> = parts.next().unwrap();
+        if parts.next().unwrap().as_rule() == Rule::match_guard {
+            continue;
+        }
+        match pattern.as_rule() {
+            Rule::wildcard_pattern | Rule::capture_pattern => return Ok(()),
+            Rule::value_pattern => {
+                if let Value::Variant(other, index) = evaluate_value_pattern(pattern, env, stdlib)?
+                {
+                    if Rc::ptr_eq(enum_type, &other) {
+                        covered[index] = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let missing: Vec<String> = enum_type
+        .variants
+        .iter()
+        .zip(covered)
+        .filter(|(_, covered)| !covered)
+        .map(|((name, _), _)| format!("{}.{}", enum_type.name, name))
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(miette::miette!(
+            "Error: Match on {} is not exhaustive: missing {}.",
+            enum_type.name,
+            missing.join(", ")
+        ))
+    }
+}
+
+/// Looks up a dotted name such as `Currency.GBP` one attribute at a time
+fn evaluate_value_pattern(
+    pattern: Pair<'static, Rule>,
+    env: &Env,
+    stdlib: &StdLib,
+) -> miette::Result<Value> {
+    let mut names = pattern.into_inner();
+    let mut value: Value = evaluate_expression(names.next().unwrap(), env, stdlib)?;
+    for name in names {
+        value = get_attribute(&value, name.as_str())?;
+    }
+    Ok(value)
+}
+
+fn bind(name: &str, value: Value, bindings: &mut Bindings) -> miette::Result<()> {
+    if bindings.iter().any(|(bound, _)| bound == name) {
+        return Err(miette::miette!(
+            "Error: Name '{}' is bound more than once in a case pattern.",
+            name
+        ));
+    }
+    bindings.push((name.to_string(), value));
+    Ok(())
+}
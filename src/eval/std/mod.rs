@@ -1,8 +1,17 @@
 pub mod std_abs;
 pub mod std_cast;
+pub mod std_dict;
+pub mod std_file;
+pub mod std_format;
+pub mod std_functional;
 pub mod std_input;
+pub mod std_json;
 pub mod std_len;
 pub mod std_list;
 pub mod std_math;
 pub mod std_print;
+pub mod std_random;
 pub mod std_range;
+pub mod std_set;
+pub mod std_string;
+pub mod std_time;
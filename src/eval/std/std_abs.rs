@@ -1,11 +1,12 @@
 // Handles the TungLang abs() built-in function
 use crate::value::Value;
 
-/// Returns the absolute value of a number or float
+/// Returns the absolute value of a number, float or decimal
 pub fn std_abs(val: &Value) -> Value {
     match val {
         Value::Number(n) => Value::Number(n.abs()),
         Value::Float(f) => Value::Float(f.abs()),
+        Value::Decimal(d) => Value::Decimal(d.abs()),
         _ => Value::Undefined,
     }
 }
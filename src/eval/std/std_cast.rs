@@ -1,10 +1,20 @@
 // Handles Python-like type conversion functions
-use crate::value::Value;
+use crate::interpreter::iterate;
+use crate::value::{Function, Value};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::rc::Rc;
+use std::str::FromStr;
 
 pub fn std_int(val: &Value) -> Value {
     match val {
         Value::Number(n) => Value::Number(*n),
         Value::Float(f) => Value::Number(*f as i64),
+        Value::Decimal(d) => d
+            .trunc()
+            .to_i64()
+            .map(Value::Number)
+            .unwrap_or(Value::Undefined),
         Value::String(s) => s
             .parse::<i64>()
             .map(Value::Number)
@@ -17,36 +27,12 @@ pub fn std_int(val: &Value) -> Value {
 }
 
 pub fn std_str(val: &Value) -> Value {
-    match val {
-        Value::String(s) => Value::String(s.clone()),
-        Value::Number(n) => Value::String(n.to_string()),
-        Value::Float(f) => Value::String(f.to_string()),
-        Value::Boolean(b) => Value::String(b.to_string()),
-        Value::Undefined => Value::String("undefined".to_string()),
-        Value::Array(arr) => {
-            let items: Vec<String> = arr
-                .iter()
-                .map(|v| match v {
-                    Value::String(s) => format!("\"{}\"", s),
-                    _ => format!("{}", v),
-                })
-                .collect();
-            Value::String(format!("[{}]", items.join(", ")))
-        }
-        Value::Dict(map) => {
-            let items: Vec<String> = map
-                .iter()
-                .map(|(k, v)| {
-                    let value_str = match v {
-                        Value::String(s) => format!("\"{}\"", s),
-                        _ => format!("{}", v),
-                    };
-                    format!("\"{}\": {}", k, value_str)
-                })
-                .collect();
-            Value::String(format!("{{{}}}", items.join(", ")))
-        }
-    }
+    Value::String(val.to_string())
+}
+
+// Python-like repr: strings come back quoted
+pub fn std_repr(val: &Value) -> Value {
+    Value::String(val.repr())
 }
 
 // Convert to float (Python-like)
@@ -54,6 +40,7 @@ pub fn std_float(val: &Value) -> Value {
     match val {
         Value::Float(f) => Value::Float(*f),
         Value::Number(n) => Value::Float(*n as f64),
+        Value::Decimal(d) => d.to_f64().map(Value::Float).unwrap_or(Value::Undefined),
         Value::String(s) => s
             .parse::<f64>()
             .map(Value::Float)
@@ -70,9 +57,90 @@ pub fn std_bool(val: &Value) -> Value {
         Value::Boolean(b) => Value::Boolean(*b),
         Value::Number(n) => Value::Boolean(*n != 0),
         Value::Float(f) => Value::Boolean(*f != 0.0),
+        Value::Decimal(d) => Value::Boolean(!d.is_zero()),
         Value::String(s) => Value::Boolean(!s.is_empty()),
-        Value::Array(arr) => Value::Boolean(!arr.is_empty()),
+        Value::Array(arr) | Value::Tuple(arr) => Value::Boolean(!arr.is_empty()),
         Value::Dict(dict) => Value::Boolean(!dict.is_empty()),
-        Value::Undefined => Value::Boolean(false),
+        Value::Set(set) => Value::Boolean(!set.is_empty()),
+        Value::Duration(duration) => Value::Boolean(!duration.is_zero()),
+        Value::Function(_)
+        | Value::Class(_)
+        | Value::Instance(_)
+        | Value::Enum(_)
+        | Value::Variant(..)
+        | Value::Module(_)
+        | Value::Date(_)
+        | Value::DateTime(_)
+        | Value::File(_) => Value::Boolean(true),
+        Value::None | Value::Undefined => Value::Boolean(false),
+    }
+}
+
+// Convert to a list or tuple (Python-like): list((1, 2)) is [1, 2], tuple("ab") is ('a', 'b')
+pub fn std_list(val: &Value) -> Value {
+    match iterate(val.clone()) {
+        Ok(items) => Value::Array(items),
+        Err(_) => Value::Undefined,
+    }
+}
+
+pub fn std_tuple(val: &Value) -> Value {
+    match iterate(val.clone()) {
+        Ok(items) => Value::Tuple(items),
+        Err(_) => Value::Undefined,
+    }
+}
+
+// isinstance function (whether the value is an instance of the class or a class inheriting
+// from it, or a variant of the enum; the conversion functions stand for the builtin types, as in isinstance(x, int).
+// A tuple checks against each of its items)
+pub fn std_isinstance(args: &[Value]) -> Value {
+    match (args.first(), args.get(1)) {
+        (Some(value), Some(Value::Tuple(types))) => {
+            let types = types.iter().map(|t| is_instance(value, t));
+            match types.collect::<Option<Vec<bool>>>() {
+                Some(results) => Value::Boolean(results.contains(&true)),
+                None => Value::Undefined,
+            }
+        }
+        (Some(value), Some(class)) => is_instance(value, class)
+            .map(Value::Boolean)
+            .unwrap_or(Value::Undefined),
+        _ => Value::Undefined,
+    }
+}
+
+fn is_instance(value: &Value, class: &Value) -> Option<bool> {
+    match (value, class) {
+        (Value::Instance(instance), Value::Class(class)) => {
+            Some(instance.class.is_subclass_of(class))
+        }
+        (Value::Variant(enum_type, _), Value::Enum(other)) => Some(Rc::ptr_eq(enum_type, other)),
+        (_, Value::Class(_) | Value::Enum(_)) => Some(false),
+        (_, Value::Function(Function::Builtin(name, _))) => match *name {
+            "int" | "float" | "str" | "bool" | "decimal" | "list" | "tuple" | "set" => {
+                Some(value.type_name() == *name)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Convert to an exact base-10 decimal. Strings are parsed digit for digit, so
+// decimal("1.17") is exactly 1.17; floats go through their shortest printed
+// form rather than their binary expansion.
+pub fn std_decimal(val: &Value) -> Value {
+    match val {
+        Value::Decimal(d) => Value::Decimal(*d),
+        Value::Number(n) => Value::Decimal(Decimal::from(*n)),
+        Value::Float(f) => Decimal::from_str(&f.to_string())
+            .map(Value::Decimal)
+            .unwrap_or(Value::Undefined),
+        Value::String(s) => Decimal::from_str(s.trim())
+            .map(Value::Decimal)
+            .unwrap_or(Value::Undefined),
+        Value::Boolean(b) => Value::Decimal(Decimal::from(*b as i64)),
+        _ => Value::Undefined,
     }
 }
@@ -0,0 +1,66 @@
+// Python-like dictionary functions for TungLang
+// Like the list functions, these return a new dict instead of changing their argument
+use crate::value::Value;
+
+// keys function (in insertion order)
+pub fn std_keys(args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::Dict(map)) => Value::Array(map.keys().cloned().collect()),
+        _ => Value::Undefined,
+    }
+}
+
+// values function (in insertion order)
+pub fn std_values(args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::Dict(map)) => Value::Array(map.values().cloned().collect()),
+        _ => Value::Undefined,
+    }
+}
+
+// items function (returns (key, value) pairs in insertion order)
+pub fn std_items(args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::Dict(map)) => Value::Array(
+            map.iter()
+                .map(|(k, v)| Value::Tuple(vec![k.clone(), v.clone()]))
+                .collect(),
+        ),
+        _ => Value::Undefined,
+    }
+}
+
+// get function (returns the value for key, or the default when it is missing)
+pub fn std_get(args: &[Value]) -> Value {
+    let default = args.get(2).cloned().unwrap_or(Value::Undefined);
+    match (args.first(), args.get(1)) {
+        (Some(Value::Dict(map)), Some(key)) => map.get(key).cloned().unwrap_or(default),
+        _ => Value::Undefined,
+    }
+}
+
+// update function (returns the dict with every entry of the second dict added or replaced)
+pub fn std_update(args: &[Value]) -> Value {
+    match (args.first(), args.get(1)) {
+        (Some(Value::Dict(map)), Some(Value::Dict(other))) => {
+            let mut map = map.clone();
+            for (k, v) in other {
+                map.insert(k.clone(), v.clone());
+            }
+            Value::Dict(map)
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// setdefault function (returns the value for key, or default when it is missing, like
+// Python's; as with the other dict functions the dict itself isn't changed)
+pub fn std_setdefault(args: &[Value]) -> Value {
+    let default = args.get(2).cloned().unwrap_or(Value::Undefined);
+    match (args.first(), args.get(1)) {
+        (Some(Value::Dict(map)), Some(key)) if key.is_hashable() => {
+            map.get(key).cloned().unwrap_or(default)
+        }
+        _ => Value::Undefined,
+    }
+}
@@ -0,0 +1,337 @@
+// Python-like file functions for TungLang: open() and the methods of the file it returns
+// With --sandbox, every path is looked up inside one directory and can't leave it
+use crate::stdlib::{Builtin, StdLib};
+use crate::value::{File, FileHandle, Keywords, Value};
+use miette::Result;
+use std::cell::RefCell;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Methods of files: f.read(), f.write(text)
+pub fn file_methods() -> [(&'static str, Builtin); 5] {
+    [
+        ("read", Builtin::Native(std_read, &[])),
+        ("readline", Builtin::Native(std_readline, &[])),
+        ("readlines", Builtin::Native(std_readlines, &[])),
+        ("write", Builtin::Native(std_write, &[])),
+        ("close", Builtin::Native(std_close, &[])),
+    ]
+}
+
+// open function (open(path) to read, open(path, "w") to write over it, or "a" to add to
+// the end of it)
+pub fn std_open(args: &[Value], keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    let (name, mode): (&str, &str) = match (args, keywords.get("mode")) {
+        ([Value::String(name)], None) => (name, "r"),
+        ([Value::String(name)], Some(Value::String(mode)))
+        | ([Value::String(name), Value::String(mode)], None) => (name, mode),
+        ([Value::String(_), _], Some(_)) => {
+            return Err(miette::miette!(
+                "Error: open() got multiple values for argument 'mode'."
+            ))
+        }
+        ([Value::String(_)], Some(other)) | ([Value::String(_), other], None) => {
+            return Err(miette::miette!(
+                "TypeError: open() mode must be a str, not {}.",
+                other.type_name()
+            ))
+        }
+        ([other, ..], _) => {
+            return Err(miette::miette!(
+                "TypeError: open() path must be a str, not {}.",
+                other.type_name()
+            ))
+        }
+        _ => {
+            return Err(miette::miette!(
+                "Error: open() takes 1 or 2 arguments, got {}.",
+                args.len()
+            ))
+        }
+    };
+    let path: PathBuf = resolve(name, stdlib)?;
+    let handle: FileHandle = match mode {
+        "r" => {
+            let text: String = fs::read_to_string(&path).map_err(|e| io_error(name, e))?;
+            FileHandle::Read { text, position: 0 }
+        }
+        "w" | "a" => {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(mode == "w")
+                .append(mode == "a")
+                .open(&path)
+                .map_err(|e| io_error(name, e))?;
+            FileHandle::Write(file)
+        }
+        _ => {
+            return Err(miette::miette!(
+                "ValueError: invalid mode '{}'; use 'r', 'w' or 'a'.",
+                mode
+            ))
+        }
+    };
+    Ok(Value::File(Rc::new(File {
+        name: name.to_string(),
+        mode: mode.to_string(),
+        handle: RefCell::new(Some(handle)),
+    })))
+}
+
+// read method (the rest of the file)
+fn std_read(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let file: &File = receiver("read", args)?;
+    read_with(file, |text| text.len())
+}
+
+// readline method (the next line, with its "\n", or "" at the end of the file)
+fn std_readline(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let file: &File = receiver("readline", args)?;
+    read_with(file, line_length)
+}
+
+// readlines method (the rest of the lines, as a list)
+fn std_readlines(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let file: &File = receiver("readlines", args)?;
+    Ok(Value::Array(read_lines(file)?))
+}
+
+// write method (adds text to the file and returns how many characters were written)
+fn std_write(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let (file, text): (&File, &str) = match args {
+        [Value::File(file), Value::String(text)] => (file, text),
+        [Value::File(_), other] => {
+            return Err(miette::miette!(
+                "TypeError: write() argument must be str, not {}.",
+                other.type_name()
+            ))
+        }
+        _ => {
+            return Err(miette::miette!(
+                "Error: write() takes exactly 1 argument, got {}.",
+                args.len().max(1) - 1
+            ))
+        }
+    };
+    match &mut *file.handle.borrow_mut() {
+        Some(FileHandle::Write(handle)) => handle
+            .write_all(text.as_bytes())
+            .map_err(|e| io_error(&file.name, e))?,
+        Some(FileHandle::Read { .. }) => {
+            return Err(miette::miette!("UnsupportedOperation: not writable."))
+        }
+        None => return Err(closed_error()),
+    }
+    Ok(Value::Number(text.chars().count() as i64))
+}
+
+// close method (closing a file again does nothing)
+fn std_close(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    receiver("close", args)?.close();
+    Ok(Value::Undefined)
+}
+
+/// The lines a for loop over the file visits: those not read yet, each with its "\n"
+pub fn read_lines(file: &File) -> Result<Vec<Value>> {
+    let mut lines: Vec<Value> = Vec::new();
+    loop {
+        match read_with(file, line_length)? {
+            Value::String(line) if line.is_empty() => return Ok(lines),
+            line => lines.push(line),
+        }
+    }
+}
+
+/// Reads the next `length(unread)` bytes of a file opened for reading
+fn read_with(file: &File, length: fn(&str) -> usize) -> Result<Value> {
+    match &mut *file.handle.borrow_mut() {
+        Some(FileHandle::Read { text, position }) => {
+            let unread: &str = &text[*position..];
+            let taken: &str = &unread[..length(unread)];
+            *position += taken.len();
+            Ok(Value::String(taken.to_string()))
+        }
+        Some(FileHandle::Write(_)) => Err(miette::miette!("UnsupportedOperation: not readable.")),
+        None => Err(closed_error()),
+    }
+}
+
+fn line_length(text: &str) -> usize {
+    text.find('\n').map_or(text.len(), |end| end + 1)
+}
+
+fn receiver<'a>(name: &str, args: &'a [Value]) -> Result<&'a File> {
+    match args {
+        [Value::File(file)] => Ok(file),
+        _ => Err(miette::miette!(
+            "Error: {}() takes no arguments, got {}.",
+            name,
+            args.len().max(1) - 1
+        )),
+    }
+}
+
+/// Finds the file a program means by `name`. Without a sandbox that is the path as given;
+/// with one, the path is taken from the sandbox directory and, once `..` and symbolic links
+/// are followed, must still be inside it.
+fn resolve(name: &str, stdlib: &StdLib) -> Result<PathBuf> {
+    let root: &Path = match stdlib.file_root() {
+        Some(root) => root,
+        None => return Ok(PathBuf::from(name)),
+    };
+    let joined: PathBuf = root.join(name);
+    // A file that doesn't exist yet can still be written, so then its directory is checked
+    let real: io::Result<PathBuf> = if fs::symlink_metadata(&joined).is_ok() {
+        fs::canonicalize(&joined)
+    } else {
+        match (joined.parent(), joined.file_name()) {
+            (Some(dir), Some(file_name)) => fs::canonicalize(dir).map(|dir| dir.join(file_name)),
+            _ => fs::canonicalize(&joined),
+        }
+    };
+    match real {
+        Ok(real) if real.starts_with(root) => Ok(real),
+        Ok(_) => Err(miette::miette!(
+            "PermissionError: '{}' is outside the sandbox directory.",
+            name
+        )),
+        Err(e) => Err(io_error(name, e)),
+    }
+}
+
+fn io_error(name: &str, e: io::Error) -> miette::Report {
+    match e.kind() {
+        io::ErrorKind::NotFound => {
+            miette::miette!("FileNotFoundError: No such file or directory: '{}'.", name)
+        }
+        io::ErrorKind::PermissionDenied => {
+            miette::miette!("PermissionError: Permission denied: '{}'.", name)
+        }
+        io::ErrorKind::InvalidData => {
+            miette::miette!("UnicodeDecodeError: '{}' is not UTF-8 text.", name)
+        }
+        _ => miette::miette!("OSError: {}: '{}'.", e, name),
+    }
+}
+
+fn closed_error() -> miette::Report {
+    miette::miette!("ValueError: I/O operation on closed file.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    /// A sandbox directory with a secret file beside it, outside the sandbox
+    struct Fixture {
+        base: PathBuf,
+        root: PathBuf,
+        outside: PathBuf,
+        stdlib: StdLib,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let base: PathBuf =
+                std::env::temp_dir().join(format!("tung-sandbox-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&base);
+            fs::create_dir_all(base.join("root")).unwrap();
+            fs::create_dir_all(base.join("outside")).unwrap();
+            fs::write(base.join("outside/secret.txt"), "secret").unwrap();
+            fs::write(base.join("root/inside.txt"), "inside").unwrap();
+            let root: PathBuf = fs::canonicalize(base.join("root")).unwrap();
+            let outside: PathBuf = fs::canonicalize(base.join("outside")).unwrap();
+            let mut stdlib: StdLib = StdLib::new();
+            stdlib.set_file_root(root.clone());
+            Fixture {
+                base,
+                root,
+                outside,
+                stdlib,
+            }
+        }
+
+        fn open(&self, name: &str, mode: &str) -> Result<Value> {
+            let args = [
+                Value::String(name.to_string()),
+                Value::String(mode.to_string()),
+            ];
+            std_open(&args, &Keywords::new(), &self.stdlib)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.base);
+        }
+    }
+
+    fn assert_outside(result: Result<Value>) {
+        match result {
+            Ok(_) => panic!("a path outside the sandbox was opened"),
+            Err(e) => assert!(
+                e.to_string().contains("outside the sandbox directory"),
+                "unexpected error: {}",
+                e
+            ),
+        }
+    }
+
+    #[test]
+    fn opens_files_inside_the_sandbox() {
+        let fixture = Fixture::new("inside");
+        let file = fixture.open("inside.txt", "r").unwrap();
+        let read = std_read(&[file], &Keywords::new(), &fixture.stdlib).unwrap();
+        assert_eq!(read, Value::String("inside".to_string()));
+    }
+
+    #[test]
+    fn rejects_parent_directory() {
+        let fixture = Fixture::new("parent");
+        assert_outside(fixture.open("../outside/secret.txt", "r"));
+        assert_outside(fixture.open("../escaped.txt", "w"));
+        assert!(!fixture.base.join("escaped.txt").exists());
+    }
+
+    #[test]
+    fn rejects_absolute_path() {
+        let fixture = Fixture::new("absolute");
+        let secret: PathBuf = fixture.outside.join("secret.txt");
+        assert_outside(fixture.open(&secret.display().to_string(), "r"));
+    }
+
+    #[test]
+    fn rejects_symlinked_file() {
+        let fixture = Fixture::new("file-link");
+        symlink(
+            fixture.outside.join("secret.txt"),
+            fixture.root.join("link.txt"),
+        )
+        .unwrap();
+        assert_outside(fixture.open("link.txt", "r"));
+        assert_outside(fixture.open("link.txt", "a"));
+        assert_eq!(
+            fs::read_to_string(fixture.outside.join("secret.txt")).unwrap(),
+            "secret"
+        );
+    }
+
+    #[test]
+    fn rejects_symlinked_directory() {
+        let fixture = Fixture::new("dir-link");
+        symlink(&fixture.outside, fixture.root.join("linked")).unwrap();
+        assert_outside(fixture.open("linked/secret.txt", "r"));
+    }
+
+    #[test]
+    fn rejects_new_file_under_symlinked_directory() {
+        let fixture = Fixture::new("new-file");
+        symlink(&fixture.outside, fixture.root.join("linked")).unwrap();
+        assert_outside(fixture.open("linked/new.txt", "w"));
+        assert!(!fixture.outside.join("new.txt").exists());
+    }
+}
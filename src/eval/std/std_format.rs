@@ -0,0 +1,509 @@
+// Handles format(), str.format and f-string formatting for TungLang
+// Implements Python's format-spec mini-language:
+//   [[fill]align][sign][z][#][0][width][grouping][.precision][type]
+use crate::stdlib::StdLib;
+use crate::value::{Keywords, Value};
+use miette::Result;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// A parsed format specification such as `>10,.2f`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatSpec {
+    pub fill: char,
+    pub align: Option<char>,
+    pub sign: char,
+    pub no_negative_zero: bool,
+    pub alternate: bool,
+    pub width: usize,
+    pub grouping: Option<char>,
+    pub precision: Option<usize>,
+    pub kind: Option<char>,
+}
+
+impl FormatSpec {
+    pub fn parse(spec: &str) -> Result<FormatSpec, String> {
+        let chars: Vec<char> = spec.chars().collect();
+        let mut i = 0;
+        let mut result = FormatSpec {
+            fill: ' ',
+            align: None,
+            sign: '-',
+            no_negative_zero: false,
+            alternate: false,
+            width: 0,
+            grouping: None,
+            precision: None,
+            kind: None,
+        };
+
+        let is_align = |c: char| matches!(c, '<' | '>' | '=' | '^');
+        let has_fill: bool = chars.len() >= 2 && is_align(chars[1]);
+        if has_fill {
+            result.fill = chars[0];
+            result.align = Some(chars[1]);
+            i = 2;
+        } else if !chars.is_empty() && is_align(chars[0]) {
+            result.align = Some(chars[0]);
+            i = 1;
+        }
+        if i < chars.len() && matches!(chars[i], '+' | '-' | ' ') {
+            result.sign = chars[i];
+            i += 1;
+        }
+        if i < chars.len() && chars[i] == 'z' {
+            result.no_negative_zero = true;
+            i += 1;
+        }
+        if i < chars.len() && chars[i] == '#' {
+            result.alternate = true;
+            i += 1;
+        }
+        if i < chars.len() && chars[i] == '0' {
+            // A leading zero means "pad with zeros after the sign", or just pad with zeros
+            // when an alignment is given without a fill
+            if result.align.is_none() {
+                result.fill = '0';
+                result.align = Some('=');
+            } else if !has_fill {
+                result.fill = '0';
+            }
+            i += 1;
+        }
+        let width_start = i;
+        while i < chars.len() && chars[i].is_ascii_digit() {
+            i += 1;
+        }
+        if i > width_start {
+            let digits: String = chars[width_start..i].iter().collect();
+            result.width = digits
+                .parse()
+                .map_err(|_| "Too many decimal digits in format string")?;
+        }
+        if i < chars.len() && matches!(chars[i], ',' | '_') {
+            result.grouping = Some(chars[i]);
+            i += 1;
+        }
+        if i < chars.len() && chars[i] == '.' {
+            i += 1;
+            let precision_start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            if i == precision_start {
+                return Err("Format specifier missing precision".to_string());
+            }
+            let digits: String = chars[precision_start..i].iter().collect();
+            result.precision = Some(
+                digits
+                    .parse()
+                    .map_err(|_| "Too many decimal digits in format string")?,
+            );
+        }
+        if i < chars.len() && "bcdeEfFgGnosxX%".contains(chars[i]) {
+            result.kind = Some(chars[i]);
+            i += 1;
+        }
+        if i != chars.len() {
+            return Err(format!("Invalid format specifier '{}'", spec));
+        }
+        Ok(result)
+    }
+}
+
+/// Formats a single value according to a format spec string
+pub fn format_value(value: &Value, spec: &str) -> Result<String, String> {
+    let spec = FormatSpec::parse(spec)?;
+    match value {
+        Value::Number(n) => format_int(*n, &spec),
+        Value::Boolean(b) if spec.kind.is_some() => format_int(*b as i64, &spec),
+        Value::Float(f) => format_float(*f, &spec, "float"),
+        Value::Decimal(d) => format_decimal(*d, &spec),
+        other => format_str(&other.to_string(), &spec, other.type_name()),
+    }
+}
+
+/// Expands a `str.format` template such as `"{} costs {0:.2f}"` or `"{name:>8}"`
+pub fn format_template(
+    template: &str,
+    args: &[Value],
+    keywords: &Keywords,
+) -> Result<String, String> {
+    let mut output = String::new();
+    let mut chars = template.chars().peekable();
+    let mut next_auto = 0;
+    let mut used_auto = false;
+    let mut used_manual = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '}' => return Err("Single '}' encountered in format string".to_string()),
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => field.push(ch),
+                        None => return Err("Single '{' encountered in format string".to_string()),
+                    }
+                }
+                let (name, spec) = match field.split_once(':') {
+                    Some((name, spec)) => (name, spec),
+                    None => (field.as_str(), ""),
+                };
+                let index = if name.is_empty() {
+                    used_auto = true;
+                    next_auto += 1;
+                    next_auto - 1
+                } else if let Ok(index) = name.parse::<usize>() {
+                    used_manual = true;
+                    index
+                } else {
+                    // A named field takes the keyword argument of that name
+                    let value = keywords
+                        .get(name)
+                        .ok_or_else(|| format!("Unknown format field '{}'", name))?;
+                    output.push_str(&format_value(value, spec)?);
+                    continue;
+                };
+                if used_auto && used_manual {
+                    return Err(
+                        "Cannot switch between automatic and manual field numbering".to_string()
+                    );
+                }
+                let value = args
+                    .get(index)
+                    .ok_or_else(|| format!("Replacement index {} out of range", index))?;
+                output.push_str(&format_value(value, spec)?);
+            }
+            _ => output.push(c),
+        }
+    }
+    Ok(output)
+}
+
+// format(value, spec) built-in
+pub fn std_format(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let (value, spec): (&Value, &str) = match args {
+        [value] => (value, ""),
+        [value, Value::String(spec)] => (value, spec),
+        [_, other] => {
+            return Err(miette::miette!(
+                "TypeError: format() spec must be a str, not {}.",
+                other.type_name()
+            ))
+        }
+        _ => {
+            return Err(miette::miette!(
+                "Error: format() takes 1 or 2 arguments, got {}.",
+                args.len()
+            ))
+        }
+    };
+    format_value(value, spec)
+        .map(Value::String)
+        .map_err(|e| miette::miette!("ValueError: {}.", e))
+}
+
+// str.format method, the template is the receiver; keyword arguments fill named fields
+pub fn std_str_format(args: &[Value], keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    match args.first() {
+        Some(Value::String(template)) => format_template(template, &args[1..], keywords)
+            .map(Value::String)
+            .map_err(|e| miette::miette!("ValueError: {}.", e)),
+        _ => Err(miette::miette!("Error: format() needs a string template.")),
+    }
+}
+
+fn format_str(s: &str, spec: &FormatSpec, type_name: &str) -> Result<String, String> {
+    if let Some(kind) = spec.kind.filter(|k| *k != 's') {
+        return Err(format!(
+            "Unknown format code '{}' for object of type '{}'",
+            kind, type_name
+        ));
+    }
+    if spec.sign != '-' {
+        return Err("Sign not allowed in string format specifier".to_string());
+    }
+    if spec.grouping.is_some() {
+        return Err("Cannot specify grouping with 's'".to_string());
+    }
+    if spec.align == Some('=') {
+        return Err("'=' alignment not allowed in string format specifier".to_string());
+    }
+    let body: String = match spec.precision {
+        Some(precision) => s.chars().take(precision).collect(),
+        None => s.to_string(),
+    };
+    Ok(pad("", "", &body, spec, '<'))
+}
+
+fn format_int(n: i64, spec: &FormatSpec) -> Result<String, String> {
+    let kind = spec.kind.unwrap_or('d');
+    if matches!(kind, 'e' | 'E' | 'f' | 'F' | 'g' | 'G' | '%') {
+        return format_float(n as f64, spec, "int");
+    }
+    if kind == 's' {
+        return Err("Unknown format code 's' for object of type 'int'".to_string());
+    }
+    if spec.precision.is_some() {
+        return Err("Precision not allowed in integer format specifier".to_string());
+    }
+    if kind == 'c' {
+        let c = u32::try_from(n)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or("%c arg not in range(0x110000)")?;
+        return Ok(pad("", "", &c.to_string(), spec, '<'));
+    }
+    let magnitude = n.unsigned_abs();
+    let (digits, prefix, group_size) = match kind {
+        'b' => (format!("{:b}", magnitude), "0b", 4),
+        'o' => (format!("{:o}", magnitude), "0o", 4),
+        'x' => (format!("{:x}", magnitude), "0x", 4),
+        'X' => (format!("{:X}", magnitude), "0X", 4),
+        _ => (magnitude.to_string(), "", 3),
+    };
+    if group_size == 4 && spec.grouping == Some(',') {
+        return Err(format!("Cannot specify ',' with '{}'", kind));
+    }
+    let prefix = if spec.alternate { prefix } else { "" };
+    let sign = sign_for(n < 0, spec);
+    let body = group_padded(&digits, "", group_size, sign.len() + prefix.len(), spec);
+    Ok(pad(sign, prefix, &body, spec, '>'))
+}
+
+fn format_float(f: f64, spec: &FormatSpec, type_name: &str) -> Result<String, String> {
+    let kind = spec.kind;
+    if let Some(kind) = kind.filter(|k| !"eEfFgGn%".contains(*k)) {
+        return Err(format!(
+            "Unknown format code '{}' for object of type '{}'",
+            kind, type_name
+        ));
+    }
+    let upper = matches!(kind, Some('E' | 'F' | 'G'));
+    let magnitude = f.abs();
+    let body = if f.is_nan() {
+        "nan".to_string()
+    } else if f.is_infinite() {
+        "inf".to_string()
+    } else {
+        match kind {
+            Some('f' | 'F') => fixed(magnitude, spec.precision.unwrap_or(6), spec.alternate),
+            Some('e' | 'E') => scientific(magnitude, spec.precision.unwrap_or(6), spec.alternate),
+            Some('g' | 'G' | 'n') => {
+                general(magnitude, spec.precision.unwrap_or(6), spec.alternate)
+            }
+            Some('%') => {
+                fixed(
+                    magnitude * 100.0,
+                    spec.precision.unwrap_or(6),
+                    spec.alternate,
+                ) + "%"
+            }
+            _ => match spec.precision {
+                // Like 'g', but fixed-point results keep at least one decimal digit
+                Some(precision) => {
+                    let s = general(magnitude, precision, spec.alternate);
+                    if s.contains(['.', 'e']) {
+                        s
+                    } else {
+                        s + ".0"
+                    }
+                }
+                None => Value::Float(magnitude).to_string(),
+            },
+        }
+    };
+    let body = if upper { body.to_uppercase() } else { body };
+    let negative = f.is_sign_negative() && !f.is_nan();
+    let negative = negative && !(spec.no_negative_zero && is_zero_text(&body));
+    let sign = sign_for(negative, spec);
+    let body = group_number(&body, sign.len(), spec);
+    Ok(pad(sign, "", &body, spec, '>'))
+}
+
+fn format_decimal(d: Decimal, spec: &FormatSpec) -> Result<String, String> {
+    // Fixed-point and percent formats stay exact, everything else goes through float
+    let exact = |d: Decimal, precision: usize| {
+        let precision = precision.min(28) as u32;
+        let mut rounded = d
+            .abs()
+            .round_dp_with_strategy(precision, RoundingStrategy::MidpointNearestEven);
+        rounded.rescale(precision);
+        let mut text = rounded.to_string();
+        if spec.alternate && precision == 0 {
+            text.push('.');
+        }
+        text
+    };
+    let body = match spec.kind {
+        Some('f' | 'F') => exact(d, spec.precision.unwrap_or(6)),
+        Some('%') => {
+            let scaled = d
+                .checked_mul(Decimal::ONE_HUNDRED)
+                .ok_or("Decimal overflow in '%' format")?;
+            exact(scaled, spec.precision.unwrap_or(6)) + "%"
+        }
+        None if spec.precision.is_none() => d.abs().to_string(),
+        Some('d' | 'b' | 'o' | 'x' | 'X' | 'c' | 's') => {
+            return Err(format!(
+                "Unknown format code '{}' for object of type 'decimal'",
+                spec.kind.unwrap()
+            ))
+        }
+        _ => return format_float(d.to_f64().unwrap_or(f64::NAN), spec, "decimal"),
+    };
+    let negative = d.is_sign_negative() && !(spec.no_negative_zero && is_zero_text(&body));
+    let sign = sign_for(negative, spec);
+    let body = group_number(&body, sign.len(), spec);
+    Ok(pad(sign, "", &body, spec, '>'))
+}
+
+fn fixed(f: f64, precision: usize, alternate: bool) -> String {
+    let mut s = format!("{:.*}", precision, f);
+    if alternate && precision == 0 {
+        s.push('.');
+    }
+    s
+}
+
+// Python writes exponents with a sign and at least two digits: 1.5e+03
+fn scientific(f: f64, precision: usize, alternate: bool) -> String {
+    let s = format!("{:.*e}", precision, f);
+    let (mantissa, exponent) = s.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let mantissa = if alternate && precision == 0 {
+        format!("{}.", mantissa)
+    } else {
+        mantissa.to_string()
+    };
+    format!(
+        "{}e{}{:02}",
+        mantissa,
+        if exponent < 0 { '-' } else { '+' },
+        exponent.abs()
+    )
+}
+
+fn general(f: f64, precision: usize, alternate: bool) -> String {
+    let precision = precision.max(1);
+    if f == 0.0 {
+        return if alternate {
+            format!("{:.*}", precision - 1, 0.0)
+        } else {
+            "0".to_string()
+        };
+    }
+    let rounded = format!("{:.*e}", precision - 1, f);
+    let exponent: i32 = rounded.split_once('e').unwrap().1.parse().unwrap();
+    let s = if exponent >= -4 && exponent < precision as i32 {
+        format!("{:.*}", (precision as i32 - 1 - exponent) as usize, f)
+    } else {
+        scientific(f, precision - 1, alternate)
+    };
+    if alternate {
+        return s;
+    }
+    // Strip insignificant trailing zeros from the mantissa
+    let (mantissa, rest) = match s.find('e') {
+        Some(pos) => s.split_at(pos),
+        None => (s.as_str(), ""),
+    };
+    let mantissa = if mantissa.contains('.') {
+        mantissa.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        mantissa
+    };
+    format!("{}{}", mantissa, rest)
+}
+
+fn is_zero_text(body: &str) -> bool {
+    !body.chars().any(|c| matches!(c, '1'..='9'))
+}
+
+fn sign_for(negative: bool, spec: &FormatSpec) -> &'static str {
+    match (negative, spec.sign) {
+        (true, _) => "-",
+        (false, '+') => "+",
+        (false, ' ') => " ",
+        _ => "",
+    }
+}
+
+// Groups the integer part of a formatted number, leaving fraction and exponent alone
+fn group_number(body: &str, used: usize, spec: &FormatSpec) -> String {
+    let end = body
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(body.len());
+    let (int_part, rest) = body.split_at(end);
+    group_padded(int_part, rest, 3, used, spec)
+}
+
+// Groups digits followed by `rest`. When zero padding (the 0 flag, or a 0 fill with '='),
+// Python groups the padding zeros too, so format(1234, "010,") is 00,001,234: zeros are
+// added until the number, with `used` characters of sign and prefix, fills the width
+fn group_padded(
+    digits: &str,
+    rest: &str,
+    group_size: usize,
+    used: usize,
+    spec: &FormatSpec,
+) -> String {
+    let zero_padded = spec.fill == '0' && spec.align == Some('=');
+    if spec.grouping.is_none() || !zero_padded || digits.is_empty() {
+        return group_digits(digits, spec.grouping, group_size) + rest;
+    }
+    let wanted: usize = spec.width.saturating_sub(used + rest.chars().count());
+    let mut count: usize = digits.len();
+    while count + (count - 1) / group_size < wanted {
+        count += 1;
+    }
+    let digits: String = "0".repeat(count - digits.len()) + digits;
+    group_digits(&digits, spec.grouping, group_size) + rest
+}
+
+fn group_digits(digits: &str, grouping: Option<char>, group_size: usize) -> String {
+    let separator = match grouping {
+        Some(separator) => separator,
+        None => return digits.to_string(),
+    };
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(group_size) {
+            grouped.push(separator);
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+fn pad(sign: &str, prefix: &str, body: &str, spec: &FormatSpec, default_align: char) -> String {
+    let len = sign.chars().count() + prefix.chars().count() + body.chars().count();
+    if spec.width <= len {
+        return format!("{}{}{}", sign, prefix, body);
+    }
+    let fill = |n: usize| spec.fill.to_string().repeat(n);
+    let padding = spec.width - len;
+    match spec.align.unwrap_or(default_align) {
+        '<' => format!("{}{}{}{}", sign, prefix, body, fill(padding)),
+        '^' => format!(
+            "{}{}{}{}{}",
+            fill(padding / 2),
+            sign,
+            prefix,
+            body,
+            fill(padding - padding / 2)
+        ),
+        '=' => format!("{}{}{}{}", sign, prefix, fill(padding), body),
+        _ => format!("{}{}{}{}", fill(padding), sign, prefix, body),
+    }
+}
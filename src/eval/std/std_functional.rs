@@ -0,0 +1,227 @@
+// Python-like functional tools for TungLang: map, filter, reduce, sorted and friends
+// Like the list functions, these return a new list instead of changing their argument
+use crate::eval::operators::compare_values;
+use crate::interpreter::{call_value, is_truthy, iterate};
+use crate::stdlib::StdLib;
+use crate::value::{Keywords, Value};
+
+// map function (calls f with one item from each iterable, stopping at the shortest)
+pub fn std_map(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> miette::Result<Value> {
+    expect_at_least("map", args, 2)?;
+    let mut columns = Vec::with_capacity(args.len() - 1);
+    for iterable in &args[1..] {
+        columns.push(iterate(iterable.clone())?.into_iter());
+    }
+    let len = columns.iter().map(|column| column.len()).min().unwrap_or(0);
+    let mut result = Vec::with_capacity(len);
+    for _ in 0..len {
+        let row: Vec<Value> = columns.iter_mut().map(|c| c.next().unwrap()).collect();
+        result.push(call_value(&args[0], row, Keywords::new(), stdlib)?);
+    }
+    Ok(Value::Array(result))
+}
+
+// filter function (keeps the items for which f returns a truthy value)
+pub fn std_filter(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> miette::Result<Value> {
+    expect_exactly("filter", args, 2)?;
+    let mut result = Vec::new();
+    for item in iterate(args[1].clone())? {
+        let keep = call_value(&args[0], vec![item.clone()], Keywords::new(), stdlib)?;
+        if is_truthy(keep) {
+            result.push(item);
+        }
+    }
+    Ok(Value::Array(result))
+}
+
+// reduce function (folds the items from the left: reduce(f, [a, b, c]) is f(f(a, b), c))
+pub fn std_reduce(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> miette::Result<Value> {
+    expect_at_least("reduce", args, 2)?;
+    if args.len() > 3 {
+        return Err(miette::miette!(
+            "Error: reduce() takes at most 3 arguments, got {}.",
+            args.len()
+        ));
+    }
+    let mut items = iterate(args[1].clone())?.into_iter();
+    let mut total = match args.get(2).cloned().or_else(|| items.next()) {
+        Some(initial) => initial,
+        None => {
+            return Err(miette::miette!(
+                "TypeError: reduce() of empty iterable with no initial value."
+            ))
+        }
+    };
+    for item in items {
+        total = call_value(&args[0], vec![total, item], Keywords::new(), stdlib)?;
+    }
+    Ok(total)
+}
+
+// sorted function (returns a sorted list; key=f sorts by f(item), reverse=true sorts largest first)
+pub fn std_sorted(args: &[Value], keywords: &Keywords, stdlib: &StdLib) -> miette::Result<Value> {
+    expect_exactly("sorted", args, 1)?;
+    let items = iterate(args[0].clone())?;
+    Ok(Value::Array(sort_values(
+        "sorted", items, keywords, stdlib,
+    )?))
+}
+
+// sort function (like sorted, but only for lists)
+pub fn std_sort(args: &[Value], keywords: &Keywords, stdlib: &StdLib) -> miette::Result<Value> {
+    expect_exactly("sort", args, 1)?;
+    match &args[0] {
+        Value::Array(items) => Ok(Value::Array(sort_values(
+            "sort",
+            items.clone(),
+            keywords,
+            stdlib,
+        )?)),
+        other => Err(miette::miette!(
+            "Error: sort() argument must be a list, not {}.",
+            other.type_name()
+        )),
+    }
+}
+
+// any function (whether any item is truthy)
+pub fn std_any(args: &[Value]) -> Value {
+    match args.first().cloned().map(iterate) {
+        Some(Ok(items)) => Value::Boolean(items.into_iter().any(is_truthy)),
+        _ => Value::Undefined,
+    }
+}
+
+// all function (whether every item is truthy; true for an empty list)
+pub fn std_all(args: &[Value]) -> Value {
+    match args.first().cloned().map(iterate) {
+        Some(Ok(items)) => Value::Boolean(items.into_iter().all(is_truthy)),
+        _ => Value::Undefined,
+    }
+}
+
+// enumerate function (returns (index, item) pairs, counting from start)
+pub fn std_enumerate(args: &[Value]) -> Value {
+    let start = match args.get(1) {
+        Some(Value::Number(start)) => *start,
+        None | Some(Value::Undefined) => 0,
+        Some(_) => return Value::Undefined,
+    };
+    match args.first().cloned().map(iterate) {
+        Some(Ok(items)) => Value::Array(
+            items
+                .into_iter()
+                .zip(start..)
+                .map(|(item, i)| Value::Tuple(vec![Value::Number(i), item]))
+                .collect(),
+        ),
+        _ => Value::Undefined,
+    }
+}
+
+// zip function (returns tuples of the items at the same position, stopping at the shortest)
+pub fn std_zip(args: &[Value]) -> Value {
+    let mut columns = Vec::with_capacity(args.len());
+    for iterable in args {
+        match iterate(iterable.clone()) {
+            Ok(items) => columns.push(items.into_iter()),
+            Err(_) => return Value::Undefined,
+        }
+    }
+    let len = columns.iter().map(|column| column.len()).min().unwrap_or(0);
+    Value::Array(
+        (0..len)
+            .map(|_| Value::Tuple(columns.iter_mut().map(|c| c.next().unwrap()).collect()))
+            .collect(),
+    )
+}
+
+// reversed function (returns the items in reverse order)
+pub fn std_reversed(args: &[Value]) -> Value {
+    match args.first().cloned().map(iterate) {
+        Some(Ok(mut items)) => {
+            items.reverse();
+            Value::Array(items)
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// Sorts by key(item), or by the items themselves, keeping equal items in their original order
+fn sort_values(
+    name: &str,
+    items: Vec<Value>,
+    keywords: &Keywords,
+    stdlib: &StdLib,
+) -> miette::Result<Vec<Value>> {
+    let reverse = match keywords.get("reverse") {
+        Some(Value::Boolean(reverse)) => *reverse,
+        None => false,
+        Some(other) => {
+            return Err(miette::miette!(
+                "Error: {}() argument 'reverse' must be a bool, not {}.",
+                name,
+                other.type_name()
+            ))
+        }
+    };
+    let keys = match keywords.get("key") {
+        Some(key) => {
+            let mut keys = Vec::with_capacity(items.len());
+            for item in &items {
+                keys.push(call_value(
+                    key,
+                    vec![item.clone()],
+                    Keywords::new(),
+                    stdlib,
+                )?);
+            }
+            keys
+        }
+        None => items.clone(),
+    };
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    let mut error = None;
+    order.sort_by(|&a, &b| {
+        if error.is_some() {
+            return std::cmp::Ordering::Equal;
+        }
+        match compare_values(&keys[a], &keys[b]) {
+            Ok(ordering) if reverse => ordering.reverse(),
+            Ok(ordering) => ordering,
+            Err(e) => {
+                error = Some(e);
+                std::cmp::Ordering::Equal
+            }
+        }
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(order.into_iter().map(|i| items[i].clone()).collect()),
+    }
+}
+
+fn expect_at_least(name: &str, args: &[Value], count: usize) -> miette::Result<()> {
+    if args.len() < count {
+        return Err(miette::miette!(
+            "Error: {}() expected at least {} arguments, got {}.",
+            name,
+            count,
+            args.len()
+        ));
+    }
+    Ok(())
+}
+
+fn expect_exactly(name: &str, args: &[Value], count: usize) -> miette::Result<()> {
+    if args.len() != count {
+        return Err(miette::miette!(
+            "Error: {}() takes exactly {} argument{}, got {}.",
+            name,
+            count,
+            if count == 1 { "" } else { "s" },
+            args.len()
+        ));
+    }
+    Ok(())
+}
@@ -2,20 +2,31 @@
 use crate::value::Value;
 use std::io::{self, Write};
 
-/// Prompts the user and returns their input as a Value (Number, Float, or String)
+/// Prompts the user and returns their input as a String, like Python
 pub fn std_input(prompt: &Value) -> Value {
-    if let Value::String(prompt) = prompt {
-        print!("{}", prompt);
-        io::stdout().flush().unwrap();
-    }
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    let input = input.trim_end_matches(['\n', '\r']);
+    Value::String(read_line(prompt))
+}
+
+/// Legacy input(): guesses whether the line is a Number, a Float or a String.
+/// Only used when running with `--legacy-input`, for scripts written before
+/// input() always returned a string.
+pub fn std_input_auto_typed(prompt: &Value) -> Value {
+    let input = read_line(prompt);
     if let Ok(n) = input.parse::<i64>() {
         Value::Number(n)
     } else if let Ok(f) = input.parse::<f64>() {
         Value::Float(f)
     } else {
-        Value::String(input.to_string())
+        Value::String(input)
     }
 }
+
+fn read_line(prompt: &Value) -> String {
+    if let Value::String(prompt) = prompt {
+        print!("{}", prompt);
+        io::stdout().flush().unwrap();
+    }
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    input.trim_end_matches(['\n', '\r']).to_string()
+}
@@ -0,0 +1,150 @@
+// JSON for TungLang, in the json module: text to values and back
+// Objects are dicts (in the order written), arrays are lists and null is None
+use crate::stdlib::{native, Builtin, NativeModule, StdLib};
+use crate::value::{Keywords, Value};
+use miette::Result;
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
+use serde_json::{Map, Number};
+
+/// The json module: import json, then json.parse(open("scores.json").read())
+pub fn json_module() -> NativeModule {
+    vec![
+        ("null", Value::None),
+        native("parse", Builtin::Native(std_parse, &[])),
+        native("stringify", Builtin::Native(std_stringify, &["indent"])),
+    ]
+}
+
+// parse function (parse('{"kaiden": [10, 8]}') gives {'kaiden': [10, 8]})
+fn std_parse(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let text: &str = match args {
+        [Value::String(text)] => text,
+        [other] => {
+            return Err(miette::miette!(
+                "TypeError: parse() argument must be str, not {}.",
+                other.type_name()
+            ))
+        }
+        _ => {
+            return Err(miette::miette!(
+                "Error: parse() takes exactly 1 argument, got {}.",
+                args.len()
+            ))
+        }
+    };
+    match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(json) => Ok(from_json(json)),
+        // serde_json's message ends with where the problem is, e.g. "at line 2 column 5"
+        Err(e) => Err(miette::miette!("JSONDecodeError: {}.", e)),
+    }
+}
+
+// stringify function (the value as JSON text, on one line or spread over lines with
+// indent=2 spaces or an indent string)
+fn std_stringify(args: &[Value], keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let value: &Value = match args {
+        [value] => value,
+        _ => {
+            return Err(miette::miette!(
+                "Error: stringify() takes exactly 1 argument, got {}.",
+                args.len()
+            ))
+        }
+    };
+    let indent: Option<String> = match keywords.get("indent") {
+        None | Some(Value::None) => None,
+        Some(Value::Number(n)) => Some(" ".repeat((*n).max(0) as usize)),
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(other) => {
+            return Err(miette::miette!(
+                "TypeError: stringify() indent must be an int or str, not {}.",
+                other.type_name()
+            ))
+        }
+    };
+    let json: serde_json::Value = to_json(value)?;
+    let text: String = match indent {
+        None => json.to_string(),
+        Some(indent) => {
+            let mut out: Vec<u8> = Vec::new();
+            let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+            let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+            json.serialize(&mut serializer)
+                .map_err(|e| miette::miette!("Error: {}.", e))?;
+            String::from_utf8(out).map_err(|e| miette::miette!("Error: {}.", e))?
+        }
+    };
+    Ok(Value::String(text))
+}
+
+fn from_json(json: serde_json::Value) -> Value {
+    match json {
+        serde_json::Value::Null => Value::None,
+        serde_json::Value::Bool(b) => Value::Boolean(b),
+        // Whole numbers too big for an int become floats, as in JavaScript
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(n) => Value::Number(n),
+            None => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => Value::String(s),
+        serde_json::Value::Array(items) => Value::Array(items.into_iter().map(from_json).collect()),
+        serde_json::Value::Object(entries) => Value::Dict(
+            entries
+                .into_iter()
+                .map(|(key, value)| (Value::String(key), from_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn to_json(value: &Value) -> Result<serde_json::Value> {
+    Ok(match value {
+        Value::None => serde_json::Value::Null,
+        Value::Boolean(b) => serde_json::Value::Bool(*b),
+        Value::Number(n) => serde_json::Value::Number((*n).into()),
+        Value::Float(f) => match Number::from_f64(*f) {
+            Some(n) => serde_json::Value::Number(n),
+            None => {
+                return Err(miette::miette!(
+                    "ValueError: {} can't be written as JSON.",
+                    value
+                ))
+            }
+        },
+        Value::String(s) => serde_json::Value::String(s.clone()),
+        Value::Array(items) | Value::Tuple(items) => {
+            serde_json::Value::Array(items.iter().map(to_json).collect::<Result<_>>()?)
+        }
+        Value::Dict(entries) => {
+            let mut object: Map<String, serde_json::Value> = Map::new();
+            for (key, value) in entries {
+                // JSON keys are always strings, so other simple keys are written as text
+                let key: String = match key {
+                    Value::String(s) => s.clone(),
+                    Value::Number(_) | Value::Float(_) | Value::Boolean(_) => key.to_string(),
+                    other => {
+                        return Err(miette::miette!(
+                            "TypeError: JSON keys must be str, int, float or bool, not {}.",
+                            other.type_name()
+                        ))
+                    }
+                };
+                object.insert(key, to_json(value)?);
+            }
+            serde_json::Value::Object(object)
+        }
+        Value::Decimal(_) => {
+            return Err(miette::miette!(
+                "TypeError: Object of type decimal is not JSON serializable; convert it with \
+                 str() or float() first."
+            ))
+        }
+        other => {
+            return Err(miette::miette!(
+                "TypeError: Object of type {} is not JSON serializable.",
+                other.type_name()
+            ))
+        }
+    })
+}
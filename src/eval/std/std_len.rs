@@ -1,12 +1,15 @@
 // Handles the TungLang len() built-in function
 use crate::value::Value;
 
-/// Returns the length of a string, array, or dict
+/// Returns the length of a string (in characters), array, tuple, dict or set, or the number of
+/// variants of an enum
 pub fn std_len(val: &Value) -> Value {
     match val {
-        Value::String(s) => Value::Number(s.len() as i64),
-        Value::Array(arr) => Value::Number(arr.len() as i64),
+        Value::String(s) => Value::Number(s.chars().count() as i64),
+        Value::Array(arr) | Value::Tuple(arr) => Value::Number(arr.len() as i64),
         Value::Dict(map) => Value::Number(map.len() as i64),
+        Value::Set(set) => Value::Number(set.len() as i64),
+        Value::Enum(enum_type) => Value::Number(enum_type.variants.len() as i64),
         _ => Value::Undefined,
     }
 }
@@ -1,5 +1,6 @@
 // file: /home/kaiden/RustroverProjects/TungLang/src/eval/std_list.rs
 // Python-like list functions for TungLang
+use crate::eval::std::std_string::char_index;
 use crate::value::Value;
 
 // append function (modifies list in-place like Python's list.append())
@@ -67,8 +68,7 @@ pub fn std_pop(args: &[Value]) -> Value {
             };
 
             if idx < arr.len() {
-                let removed = arr.remove(idx);
-                removed
+                arr.remove(idx)
             } else {
                 Value::Undefined
             }
@@ -77,7 +77,7 @@ pub fn std_pop(args: &[Value]) -> Value {
     }
 }
 
-// index function (returns the index of the first occurrence of value)
+// index function (returns the index of the first occurrence of value, in characters for strings)
 pub fn std_index(args: &[Value]) -> Value {
     if args.len() < 2 {
         return Value::Undefined;
@@ -94,8 +94,8 @@ pub fn std_index(args: &[Value]) -> Value {
         }
         Value::String(s) => {
             if let Value::String(substr) = &args[1] {
-                match s.find(substr) {
-                    Some(idx) => Value::Number(idx as i64),
+                match s.find(substr.as_str()) {
+                    Some(idx) => Value::Number(char_index(s, idx) as i64),
                     None => Value::Number(-1),
                 }
             } else {
@@ -105,31 +105,3 @@ pub fn std_index(args: &[Value]) -> Value {
         _ => Value::Undefined,
     }
 }
-
-// sort function (sorts a list in-place)
-pub fn std_sort(args: &[Value]) -> Value {
-    if args.is_empty() {
-        return Value::Undefined;
-    }
-
-    match args[0].clone() {
-        Value::Array(mut arr) => {
-            arr.sort_by(|a, b| match (a, b) {
-                (Value::Number(n1), Value::Number(n2)) => n1.cmp(n2),
-                (Value::Float(f1), Value::Float(f2)) => {
-                    f1.partial_cmp(f2).unwrap_or(std::cmp::Ordering::Equal)
-                }
-                (Value::Number(n), Value::Float(f)) => (*n as f64)
-                    .partial_cmp(f)
-                    .unwrap_or(std::cmp::Ordering::Equal),
-                (Value::Float(f), Value::Number(n)) => f
-                    .partial_cmp(&(*n as f64))
-                    .unwrap_or(std::cmp::Ordering::Equal),
-                (Value::String(s1), Value::String(s2)) => s1.cmp(s2),
-                _ => std::cmp::Ordering::Equal,
-            });
-            Value::Array(arr)
-        }
-        _ => Value::Undefined,
-    }
-}
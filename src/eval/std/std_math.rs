@@ -1,6 +1,11 @@
 // file: /home/kaiden/RustroverProjects/TungLang/src/eval/std_math.rs
 // Python-like math functions for TungLang
-use crate::value::Value;
+use crate::stdlib::{native, Builtin, NativeModule, StdLib};
+use crate::value::{Keywords, Value};
+use miette::Result;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::f64::consts;
 
 // min function
 pub fn std_min(args: &[Value]) -> Value {
@@ -126,7 +131,9 @@ pub fn std_sum(args: &[Value]) -> Value {
 
             let mut sum_int = 0i64;
             let mut sum_float = 0.0f64;
+            let mut sum_decimal = Decimal::ZERO;
             let mut is_float = false;
+            let mut is_decimal = false;
 
             for value in array {
                 match value {
@@ -138,18 +145,36 @@ pub fn std_sum(args: &[Value]) -> Value {
                         }
                     }
                     Value::Float(f) => {
+                        if is_decimal {
+                            return Value::Undefined; // Decimals don't mix with floats
+                        }
                         if !is_float {
                             sum_float = sum_int as f64;
                             is_float = true;
                         }
                         sum_float += f;
                     }
+                    Value::Decimal(d) => {
+                        if is_float {
+                            return Value::Undefined; // Decimals don't mix with floats
+                        }
+                        is_decimal = true;
+                        sum_decimal = match sum_decimal.checked_add(*d) {
+                            Some(total) => total,
+                            None => return Value::Undefined,
+                        };
+                    }
                     _ => return Value::Undefined, // Non-numeric value
                 }
             }
 
             if is_float {
                 Value::Float(sum_float)
+            } else if is_decimal {
+                sum_decimal
+                    .checked_add(Decimal::from(sum_int))
+                    .map(Value::Decimal)
+                    .unwrap_or(Value::Undefined)
             } else {
                 Value::Number(sum_int)
             }
@@ -159,6 +184,9 @@ pub fn std_sum(args: &[Value]) -> Value {
 }
 
 // round function
+// Floats round half away from zero. Decimals round half-to-even by default, or
+// half-up when "half_up" is passed as a third argument, and always keep exactly
+// `digits` decimal places so round(decimal("12.5"), 2) prints as 12.50.
 pub fn std_round(args: &[Value]) -> Value {
     if args.is_empty() {
         return Value::Undefined;
@@ -184,6 +212,388 @@ pub fn std_round(args: &[Value]) -> Value {
                 Value::Float((f * factor).round() / factor)
             }
         }
+        Value::Decimal(d) => {
+            let strategy = match args.get(2) {
+                None | Some(Value::Undefined) => RoundingStrategy::MidpointNearestEven,
+                Some(Value::String(mode)) => match mode.as_str() {
+                    "half_even" => RoundingStrategy::MidpointNearestEven,
+                    "half_up" => RoundingStrategy::MidpointAwayFromZero,
+                    _ => return Value::Undefined,
+                },
+                Some(_) => return Value::Undefined,
+            };
+            if matches!(args.get(1), None | Some(Value::Undefined)) {
+                return d
+                    .round_dp_with_strategy(0, strategy)
+                    .to_i64()
+                    .map(Value::Number)
+                    .unwrap_or(Value::Undefined);
+            }
+            if digits < 0 {
+                return Value::Undefined;
+            }
+            let mut rounded = d.round_dp_with_strategy(digits as u32, strategy);
+            rounded.rescale(digits as u32);
+            Value::Decimal(rounded)
+        }
         _ => Value::Undefined,
     }
 }
+
+// A math function of one float, such as sqrt, checked for domain and range errors
+macro_rules! unary {
+    ($name:literal, $f:expr) => {
+        Builtin::Native(
+            |args: &[Value], _keywords: &Keywords, _stdlib: &StdLib| {
+                let [x] = float_args::<1>($name, args)?;
+                checked($name, args, $f(x))
+            },
+            &[],
+        )
+    };
+}
+/// The math module: import math, then math.pi or math.sqrt(2)
+pub fn math_module() -> NativeModule {
+    vec![
+        ("pi", Value::Float(consts::PI)),
+        ("e", Value::Float(consts::E)),
+        ("tau", Value::Float(consts::TAU)),
+        ("inf", Value::Float(f64::INFINITY)),
+        ("nan", Value::Float(f64::NAN)),
+        native("sqrt", unary!("sqrt", f64::sqrt)),
+        native("exp", unary!("exp", f64::exp)),
+        native("sin", unary!("sin", f64::sin)),
+        native("cos", unary!("cos", f64::cos)),
+        native("tan", unary!("tan", f64::tan)),
+        native("asin", unary!("asin", f64::asin)),
+        native("acos", unary!("acos", f64::acos)),
+        native("atan", unary!("atan", f64::atan)),
+        native("degrees", unary!("degrees", f64::to_degrees)),
+        native("radians", unary!("radians", f64::to_radians)),
+        native("log10", Builtin::Native(std_log10, &[])),
+        native("log2", Builtin::Native(std_log2, &[])),
+        native("atan2", Builtin::Native(std_atan2, &[])),
+        native("pow", Builtin::Native(std_pow, &[])),
+        native("log", Builtin::Native(std_log, &[])),
+        native("hypot", Builtin::Native(std_hypot, &[])),
+        native("floor", Builtin::Native(std_floor, &[])),
+        native("ceil", Builtin::Native(std_ceil, &[])),
+        native("trunc", Builtin::Native(std_trunc, &[])),
+        native("isnan", Builtin::Native(std_isnan, &[])),
+        native("isinf", Builtin::Native(std_isinf, &[])),
+        native("isfinite", Builtin::Native(std_isfinite, &[])),
+        native("gcd", Builtin::Native(std_gcd, &[])),
+        native("lcm", Builtin::Native(std_lcm, &[])),
+        native("factorial", Builtin::Native(std_factorial, &[])),
+        native("comb", Builtin::Native(std_comb, &[])),
+        native("perm", Builtin::Native(std_perm, &[])),
+    ]
+}
+
+// atan2 function (the angle of the point (x, y), in radians)
+fn std_atan2(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let [y, x] = float_args::<2>("atan2", args)?;
+    checked("atan2", args, y.atan2(x))
+}
+
+// pow function (always a float, unlike **)
+fn std_pow(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let [x, y] = float_args::<2>("pow", args)?;
+    if x == 0.0 && y < 0.0 {
+        return Err(domain_error("pow", args));
+    }
+    checked("pow", args, x.powf(y))
+}
+
+// log function (natural logarithm, or to the given base)
+fn std_log(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let (x, base) = match args {
+        [x] => (to_float("log", x)?, None),
+        [x, base] => (to_float("log", x)?, Some(to_float("log", base)?)),
+        _ => return Err(arity_error("log", "1 or 2 arguments", args.len())),
+    };
+    match base {
+        _ if x <= 0.0 => Err(domain_error("log", args)),
+        Some(base) if base <= 0.0 || base == 1.0 => Err(domain_error("log", args)),
+        Some(base) => checked("log", args, x.ln() / base.ln()),
+        None => checked("log", args, x.ln()),
+    }
+}
+
+// log10 function
+fn std_log10(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let [x] = float_args::<1>("log10", args)?;
+    if x <= 0.0 {
+        return Err(domain_error("log10", args));
+    }
+    checked("log10", args, x.log10())
+}
+
+// log2 function
+fn std_log2(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let [x] = float_args::<1>("log2", args)?;
+    if x <= 0.0 {
+        return Err(domain_error("log2", args));
+    }
+    checked("log2", args, x.log2())
+}
+
+/// Returns a math function's float result, reporting NaN from inputs that weren't NaN as a
+/// domain error (sqrt(-1)) and infinity from finite inputs as a range error (exp(1000))
+fn checked(name: &str, args: &[Value], result: f64) -> Result<Value> {
+    let inputs: Vec<f64> = args
+        .iter()
+        .map(|arg| to_float(name, arg))
+        .collect::<Result<_>>()?;
+    if result.is_nan() && !inputs.iter().any(|x| x.is_nan()) {
+        return Err(domain_error(name, args));
+    }
+    if result.is_infinite() && inputs.iter().all(|x| x.is_finite()) {
+        return Err(miette::miette!(
+            "OverflowError: math range error in {}().",
+            name
+        ));
+    }
+    Ok(Value::Float(result))
+}
+
+// hypot function (the distance from the origin to the point with these coordinates)
+fn std_hypot(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let mut total: f64 = 0.0;
+    for arg in args {
+        total = total.hypot(to_float("hypot", arg)?);
+    }
+    Ok(Value::Float(total))
+}
+
+// floor function (the largest int not above x)
+fn std_floor(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    to_integral("floor", args, f64::floor, Decimal::floor)
+}
+
+// ceil function (the smallest int not below x)
+fn std_ceil(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    to_integral("ceil", args, f64::ceil, Decimal::ceil)
+}
+
+// trunc function (x without its fractional part, as an int)
+fn std_trunc(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    to_integral("trunc", args, f64::trunc, Decimal::trunc)
+}
+
+fn to_integral(
+    name: &str,
+    args: &[Value],
+    float: fn(f64) -> f64,
+    decimal: fn(&Decimal) -> Decimal,
+) -> Result<Value> {
+    let rounded: f64 = match args {
+        [Value::Number(n)] => return Ok(Value::Number(*n)),
+        [Value::Boolean(b)] => return Ok(Value::Number(*b as i64)),
+        [Value::Decimal(d)] => {
+            return decimal(d)
+                .to_i64()
+                .map(Value::Number)
+                .ok_or_else(|| miette::miette!("OverflowError: {}() result is too large.", name))
+        }
+        [x] => float(to_float(name, x)?),
+        _ => return Err(arity_error(name, "exactly 1 argument", args.len())),
+    };
+    if rounded.is_nan() {
+        return Err(miette::miette!(
+            "ValueError: cannot convert float NaN to integer."
+        ));
+    }
+    // i64::MAX as f64 rounds up to 2^63, which is already out of range
+    if rounded.is_infinite() || rounded < i64::MIN as f64 || rounded >= i64::MAX as f64 {
+        return Err(miette::miette!(
+            "OverflowError: {}() result is too large for an int.",
+            name
+        ));
+    }
+    Ok(Value::Number(rounded as i64))
+}
+
+// isnan function
+fn std_isnan(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let [x] = float_args::<1>("isnan", args)?;
+    Ok(Value::Boolean(x.is_nan()))
+}
+
+// isinf function
+fn std_isinf(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let [x] = float_args::<1>("isinf", args)?;
+    Ok(Value::Boolean(x.is_infinite()))
+}
+
+// isfinite function (neither infinite nor NaN)
+fn std_isfinite(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let [x] = float_args::<1>("isfinite", args)?;
+    Ok(Value::Boolean(x.is_finite()))
+}
+
+// gcd function (the greatest common divisor of any number of ints; gcd() is 0)
+fn std_gcd(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let mut result: u64 = 0;
+    for arg in args {
+        result = gcd(result, to_int("gcd", arg)?.unsigned_abs());
+    }
+    int_result("gcd", result)
+}
+
+// lcm function (the least common multiple of any number of ints; lcm() is 1)
+fn std_lcm(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let mut result: u64 = 1;
+    for arg in args {
+        let n: u64 = to_int("lcm", arg)?.unsigned_abs();
+        if n == 0 || result == 0 {
+            result = 0;
+            continue;
+        }
+        result = (result / gcd(result, n))
+            .checked_mul(n)
+            .ok_or_else(|| miette::miette!("OverflowError: lcm() result is too large."))?;
+    }
+    int_result("lcm", result)
+}
+
+// Works on sizes, since the size of the smallest int doesn't fit in an int
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn int_result(name: &str, n: u64) -> Result<Value> {
+    i64::try_from(n)
+        .map(Value::Number)
+        .map_err(|_| miette::miette!("OverflowError: {}() result is too large.", name))
+}
+
+// factorial function (n! for an int n >= 0)
+fn std_factorial(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let n: i64 = match args {
+        [n] => to_int("factorial", n)?,
+        _ => return Err(arity_error("factorial", "exactly 1 argument", args.len())),
+    };
+    if n < 0 {
+        return Err(miette::miette!(
+            "ValueError: factorial() not defined for negative values."
+        ));
+    }
+    falling_factorial("factorial", n, n)
+}
+
+// comb function (the number of ways to choose k items from n, ignoring order)
+fn std_comb(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let (n, k) = n_and_k("comb", args)?;
+    if k > n {
+        return Ok(Value::Number(0));
+    }
+    // Multiplying and dividing in turn keeps every step a whole number
+    let k: i64 = k.min(n - k);
+    let mut result: i128 = 1;
+    for i in 0..k {
+        result = result * (n - i) as i128 / (i + 1) as i128;
+        if result > i64::MAX as i128 {
+            return Err(miette::miette!(
+                "OverflowError: comb() result is too large."
+            ));
+        }
+    }
+    Ok(Value::Number(result as i64))
+}
+
+// perm function (the number of ways to choose k items from n in order; perm(n) is n!)
+fn std_perm(args: &[Value], keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    if let [_] = args {
+        return std_factorial(args, keywords, stdlib);
+    }
+    let (n, k) = n_and_k("perm", args)?;
+    if k > n {
+        return Ok(Value::Number(0));
+    }
+    falling_factorial("perm", n, k)
+}
+
+// n * (n - 1) * ... with k factors
+fn falling_factorial(name: &str, n: i64, k: i64) -> Result<Value> {
+    let mut result: i64 = 1;
+    for factor in (n - k + 1)..=n {
+        result = result
+            .checked_mul(factor)
+            .ok_or_else(|| miette::miette!("OverflowError: {}() result is too large.", name))?;
+    }
+    Ok(Value::Number(result))
+}
+
+fn n_and_k(name: &str, args: &[Value]) -> Result<(i64, i64)> {
+    let (n, k) = match args {
+        [n, k] => (to_int(name, n)?, to_int(name, k)?),
+        _ => return Err(arity_error(name, "exactly 2 arguments", args.len())),
+    };
+    if n < 0 || k < 0 {
+        return Err(miette::miette!(
+            "ValueError: {}() arguments must be non-negative integers.",
+            name
+        ));
+    }
+    Ok((n, k))
+}
+
+/// Reads a math function's argument as a float; ints, decimals and booleans are converted
+fn to_float(name: &str, value: &Value) -> Result<f64> {
+    match value {
+        Value::Number(n) => Ok(*n as f64),
+        Value::Float(f) => Ok(*f),
+        Value::Boolean(b) => Ok(*b as i64 as f64),
+        Value::Decimal(d) => Ok(d.to_f64().unwrap_or(f64::NAN)),
+        other => Err(miette::miette!(
+            "TypeError: {}() argument must be a number, not {}.",
+            name,
+            other.type_name()
+        )),
+    }
+}
+
+/// Reads a math function's argument as an int; like Python, floats aren't accepted
+fn to_int(name: &str, value: &Value) -> Result<i64> {
+    match value {
+        Value::Number(n) => Ok(*n),
+        Value::Boolean(b) => Ok(*b as i64),
+        other => Err(miette::miette!(
+            "TypeError: {}() argument must be an int, not {}.",
+            name,
+            other.type_name()
+        )),
+    }
+}
+
+fn float_args<const N: usize>(name: &str, args: &[Value]) -> Result<[f64; N]> {
+    if args.len() != N {
+        let expected = match N {
+            1 => "exactly 1 argument".to_string(),
+            n => format!("exactly {} arguments", n),
+        };
+        return Err(arity_error(name, &expected, args.len()));
+    }
+    let mut floats: [f64; N] = [0.0; N];
+    for (float, arg) in floats.iter_mut().zip(args) {
+        *float = to_float(name, arg)?;
+    }
+    Ok(floats)
+}
+
+fn arity_error(name: &str, expected: &str, got: usize) -> miette::Report {
+    miette::miette!("Error: {}() takes {}, got {}.", name, expected, got)
+}
+
+fn domain_error(name: &str, args: &[Value]) -> miette::Report {
+    let args: Vec<String> = args.iter().map(Value::repr).collect();
+    miette::miette!(
+        "ValueError: math domain error in {}({}).",
+        name,
+        args.join(", ")
+    )
+}
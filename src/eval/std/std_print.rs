@@ -1,15 +1,37 @@
 // Handles the TungLang print() built-in function
-use crate::value::Value;
+use crate::stdlib::StdLib;
+use crate::value::{Keywords, Value};
+use std::io::{self, Write};
 
-/// Prints a Value to stdout
-pub fn std_print(val: &Value) {
-    match val {
-        Value::String(s) => println!("{}", s),
-        Value::Number(n) => println!("{}", n),
-        Value::Float(f) => println!("{}", f),
-        Value::Boolean(b) => println!("{}", b),
-        Value::Array(arr) => println!("{:?}", arr),
-        Value::Dict(map) => println!("{:?}", map),
-        Value::Undefined => println!("undefined"),
+/// Prints Values to stdout separated by `sep` and followed by `end`, like Python
+pub fn std_print(values: &[Value], sep: &str, end: &str) {
+    let text: Vec<String> = values.iter().map(Value::to_string).collect();
+    print!("{}{}", text.join(sep), end);
+    if !end.ends_with('\n') {
+        io::stdout().flush().unwrap();
+    }
+}
+
+/// print() as a function: print(values..., sep=" ", end="\n")
+pub fn std_print_keywords(
+    args: &[Value],
+    keywords: &Keywords,
+    _stdlib: &StdLib,
+) -> miette::Result<Value> {
+    let sep = string_keyword(keywords, "sep")?.unwrap_or(" ");
+    let end = string_keyword(keywords, "end")?.unwrap_or("\n");
+    std_print(args, sep, end);
+    Ok(Value::Number(0))
+}
+
+fn string_keyword<'a>(keywords: &'a Keywords, name: &str) -> miette::Result<Option<&'a str>> {
+    match keywords.get(name) {
+        Some(Value::String(text)) => Ok(Some(text)),
+        Some(other) => Err(miette::miette!(
+            "Error: print() argument '{}' must be a string, not {}.",
+            name,
+            other.type_name()
+        )),
+        None => Ok(None),
     }
 }
@@ -0,0 +1,178 @@
+// Python-like random functions for TungLang, in the random module
+// Every run is different unless a seed is given, with random.seed(n) or the --seed flag
+use crate::interpreter::iterate;
+use crate::stdlib::{native, Builtin, NativeModule, StdLib};
+use crate::value::{Keywords, Value};
+use miette::Result;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The random module: import random, then random.randint(1, 6)
+pub fn random_module() -> NativeModule {
+    vec![
+        native("seed", Builtin::Native(std_seed, &[])),
+        native("random", Builtin::Native(std_random, &[])),
+        native("uniform", Builtin::Native(std_uniform, &[])),
+        native("randint", Builtin::Native(std_randint, &[])),
+        native("choice", Builtin::Native(std_choice, &[])),
+        native("shuffle", Builtin::Native(std_shuffle, &[])),
+        native("sample", Builtin::Native(std_sample, &[])),
+    ]
+}
+
+/// A seed that differs from run to run, for when the program doesn't pick one
+pub fn time_seed() -> u64 {
+    let nanos: u128 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    (nanos as u64) ^ ((std::process::id() as u64) << 32)
+}
+
+// seed function (seed(n) makes the numbers that follow the same on every run; seed() undoes it)
+fn std_seed(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    let seed: u64 = match args {
+        [] => time_seed(),
+        [Value::Number(n)] => *n as u64,
+        [other] => {
+            return Err(miette::miette!(
+                "TypeError: seed() argument must be an int, not {}.",
+                other.type_name()
+            ))
+        }
+        _ => return Err(arity_error("seed", "at most 1 argument", args.len())),
+    };
+    stdlib.seed_random(seed);
+    Ok(Value::Undefined)
+}
+
+// random function (a float from 0.0 up to but not including 1.0)
+fn std_random(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    if !args.is_empty() {
+        return Err(arity_error("random", "no arguments", args.len()));
+    }
+    Ok(Value::Float(random_float(stdlib)))
+}
+
+// uniform function (a float between a and b)
+fn std_uniform(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    let (a, b) = match args {
+        [a, b] => (to_float("uniform", a)?, to_float("uniform", b)?),
+        _ => return Err(arity_error("uniform", "exactly 2 arguments", args.len())),
+    };
+    Ok(Value::Float(a + (b - a) * random_float(stdlib)))
+}
+
+// randint function (an int from a to b, including both)
+fn std_randint(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    let (a, b) = match args {
+        [Value::Number(a), Value::Number(b)] => (*a, *b),
+        [_, _] => {
+            let other = args.iter().find(|arg| !matches!(arg, Value::Number(_)));
+            return Err(miette::miette!(
+                "TypeError: randint() arguments must be ints, not {}.",
+                other.unwrap().type_name()
+            ));
+        }
+        _ => return Err(arity_error("randint", "exactly 2 arguments", args.len())),
+    };
+    if a > b {
+        return Err(miette::miette!(
+            "ValueError: empty range for randint({}, {}).",
+            a,
+            b
+        ));
+    }
+    let span: u128 = (b as i128 - a as i128 + 1) as u128;
+    Ok(Value::Number(
+        (a as i128 + random_below(stdlib, span) as i128) as i64,
+    ))
+}
+
+// choice function (one item of a list, tuple, string or other iterable)
+fn std_choice(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    let items: Vec<Value> = match args {
+        [items] => iterate(items.clone())?,
+        _ => return Err(arity_error("choice", "exactly 1 argument", args.len())),
+    };
+    if items.is_empty() {
+        return Err(miette::miette!(
+            "IndexError: Cannot choose from an empty sequence."
+        ));
+    }
+    let index = random_below(stdlib, items.len() as u128) as usize;
+    Ok(items[index].clone())
+}
+
+// shuffle function (returns the list in a random order, like the other list functions it
+// doesn't change its argument: deck = random.shuffle(deck))
+fn std_shuffle(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    let mut items: Vec<Value> = match args {
+        [Value::Array(items)] => items.clone(),
+        [other] => {
+            return Err(miette::miette!(
+                "TypeError: shuffle() argument must be a list, not {}.",
+                other.type_name()
+            ))
+        }
+        _ => return Err(arity_error("shuffle", "exactly 1 argument", args.len())),
+    };
+    // Fisher-Yates: each item swaps with a random one at or after it
+    let len = items.len();
+    for i in 0..len {
+        let j = i + random_below(stdlib, (len - i) as u128) as usize;
+        items.swap(i, j);
+    }
+    Ok(Value::Array(items))
+}
+
+// sample function (k different items, picked at random, as a list)
+fn std_sample(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    let (mut items, k) = match args {
+        [items, Value::Number(k)] => (iterate(items.clone())?, *k),
+        [_, other] => {
+            return Err(miette::miette!(
+                "TypeError: sample() count must be an int, not {}.",
+                other.type_name()
+            ))
+        }
+        _ => return Err(arity_error("sample", "exactly 2 arguments", args.len())),
+    };
+    if k < 0 || k as usize > items.len() {
+        return Err(miette::miette!(
+            "ValueError: Sample larger than population or is negative."
+        ));
+    }
+    let k = k as usize;
+    let len = items.len();
+    for i in 0..k {
+        let j = i + random_below(stdlib, (len - i) as u128) as usize;
+        items.swap(i, j);
+    }
+    items.truncate(k);
+    Ok(Value::Array(items))
+}
+
+/// A float from 0.0 up to but not including 1.0, from the top 53 bits of the next number
+fn random_float(stdlib: &StdLib) -> f64 {
+    (stdlib.next_random() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// An int from 0 up to but not including `span`, which may be as big as 2^64
+fn random_below(stdlib: &StdLib, span: u128) -> u128 {
+    (stdlib.next_random() as u128 * span) >> 64
+}
+
+fn to_float(name: &str, value: &Value) -> Result<f64> {
+    match value {
+        Value::Number(n) => Ok(*n as f64),
+        Value::Float(f) => Ok(*f),
+        other => Err(miette::miette!(
+            "TypeError: {}() arguments must be numbers, not {}.",
+            name,
+            other.type_name()
+        )),
+    }
+}
+
+fn arity_error(name: &str, expected: &str, got: usize) -> miette::Report {
+    miette::miette!("Error: {}() takes {}, got {}.", name, expected, got)
+}
@@ -0,0 +1,68 @@
+// Python-like set functions for TungLang
+// Like the list and dict functions, these return a new set instead of changing their argument
+use crate::eval::operators::check_hashable;
+use crate::interpreter::iterate;
+use crate::stdlib::StdLib;
+use crate::value::{Keywords, Value};
+use indexmap::IndexSet;
+
+// set function (set() is an empty set, set(items) the distinct items in order)
+pub fn std_set(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> miette::Result<Value> {
+    let mut set = IndexSet::new();
+    if let Some(items) = args.first() {
+        for item in iterate(items.clone())? {
+            check_hashable(&item)?;
+            set.insert(item);
+        }
+    }
+    Ok(Value::Set(set))
+}
+
+// add function (returns the set with item added)
+pub fn std_add(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> miette::Result<Value> {
+    let (mut set, item) = set_and_item("add", args)?;
+    set.insert(item);
+    Ok(Value::Set(set))
+}
+
+// remove function (returns the set without item, which must be in it)
+pub fn std_remove(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> miette::Result<Value> {
+    let (mut set, item) = set_and_item("remove", args)?;
+    if !set.shift_remove(&item) {
+        return Err(miette::miette!(
+            "KeyError: {} not found in set.",
+            item.repr()
+        ));
+    }
+    Ok(Value::Set(set))
+}
+
+// discard function (returns the set without item, if it was there)
+pub fn std_discard(
+    args: &[Value],
+    _keywords: &Keywords,
+    _stdlib: &StdLib,
+) -> miette::Result<Value> {
+    let (mut set, item) = set_and_item("discard", args)?;
+    set.shift_remove(&item);
+    Ok(Value::Set(set))
+}
+
+fn set_and_item(name: &str, args: &[Value]) -> miette::Result<(IndexSet<Value>, Value)> {
+    match args {
+        [Value::Set(set), item] => {
+            check_hashable(item)?;
+            Ok((set.clone(), item.clone()))
+        }
+        [other, _] => Err(miette::miette!(
+            "Error: {}() argument must be a set, not {}.",
+            name,
+            other.type_name()
+        )),
+        _ => Err(miette::miette!(
+            "Error: {}() takes exactly 2 arguments, got {}.",
+            name,
+            args.len()
+        )),
+    }
+}
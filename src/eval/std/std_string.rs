@@ -0,0 +1,338 @@
+// Python-like string functions for TungLang
+// Positions, widths and counts are all measured in characters, not bytes
+use crate::stdlib::{native, Builtin, NativeModule};
+use crate::value::Value;
+
+// upper function
+pub fn std_upper(args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::String(s)) => Value::String(s.to_uppercase()),
+        _ => Value::Undefined,
+    }
+}
+
+// lower function
+pub fn std_lower(args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::String(s)) => Value::String(s.to_lowercase()),
+        _ => Value::Undefined,
+    }
+}
+
+// strip function (removes whitespace, or the given characters, from both ends)
+pub fn std_strip(args: &[Value]) -> Value {
+    match (args.first(), args.get(1)) {
+        (Some(Value::String(s)), None) => Value::String(s.trim().to_string()),
+        (Some(Value::String(s)), Some(Value::String(chars))) => {
+            let chars: Vec<char> = chars.chars().collect();
+            Value::String(s.trim_matches(chars.as_slice()).to_string())
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// split function (splits on whitespace, or on a separator at most maxsplit times)
+pub fn std_split(args: &[Value]) -> Value {
+    let s = match args.first() {
+        Some(Value::String(s)) => s,
+        _ => return Value::Undefined,
+    };
+    let max_split = match args.get(2) {
+        Some(Value::Number(n)) if *n >= 0 => Some(*n as usize),
+        Some(Value::Number(_)) | None => None,
+        Some(_) => return Value::Undefined,
+    };
+    let parts: Vec<&str> = match args.get(1) {
+        None | Some(Value::Undefined) => match max_split {
+            Some(n) => s.trim_start().splitn(n + 1, char::is_whitespace).collect(),
+            None => s.split_whitespace().collect(),
+        },
+        Some(Value::String(sep)) if !sep.is_empty() => match max_split {
+            Some(n) => s.splitn(n + 1, sep.as_str()).collect(),
+            None => s.split(sep.as_str()).collect(),
+        },
+        _ => return Value::Undefined,
+    };
+    Value::Array(
+        parts
+            .into_iter()
+            .map(|part| Value::String(part.to_string()))
+            .collect(),
+    )
+}
+
+// join function (join(sep, items), like Python's sep.join(items))
+pub fn std_join(args: &[Value]) -> Value {
+    match (args.first(), args.get(1)) {
+        (Some(Value::String(sep)), Some(Value::Array(items))) => {
+            let mut parts = Vec::with_capacity(items.len());
+            for item in items {
+                match item {
+                    Value::String(s) => parts.push(s.as_str()),
+                    _ => return Value::Undefined,
+                }
+            }
+            Value::String(parts.join(sep))
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// replace function (replaces every occurrence, or only the first count)
+pub fn std_replace(args: &[Value]) -> Value {
+    match (args.first(), args.get(1), args.get(2), args.get(3)) {
+        (Some(Value::String(s)), Some(Value::String(old)), Some(Value::String(new)), None) => {
+            Value::String(s.replace(old.as_str(), new))
+        }
+        (
+            Some(Value::String(s)),
+            Some(Value::String(old)),
+            Some(Value::String(new)),
+            Some(Value::Number(count)),
+        ) => {
+            if *count < 0 {
+                Value::String(s.replace(old.as_str(), new))
+            } else {
+                Value::String(s.replacen(old.as_str(), new, *count as usize))
+            }
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// startswith function
+pub fn std_startswith(args: &[Value]) -> Value {
+    match (args.first(), args.get(1)) {
+        (Some(Value::String(s)), Some(Value::String(prefix))) => {
+            Value::Boolean(s.starts_with(prefix.as_str()))
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// endswith function
+pub fn std_endswith(args: &[Value]) -> Value {
+    match (args.first(), args.get(1)) {
+        (Some(Value::String(s)), Some(Value::String(suffix))) => {
+            Value::Boolean(s.ends_with(suffix.as_str()))
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// find function (returns the character index of the first occurrence, or -1)
+pub fn std_find(args: &[Value]) -> Value {
+    let start = match args.get(2) {
+        Some(Value::Number(n)) => char_position(args.first(), *n),
+        None => 0,
+        Some(_) => return Value::Undefined,
+    };
+    match (args.first(), args.get(1)) {
+        (Some(Value::String(s)), Some(Value::String(substr))) => {
+            let offset = byte_offset(s, start);
+            match s[offset..].find(substr.as_str()) {
+                Some(idx) => Value::Number(char_index(s, offset + idx) as i64),
+                None => Value::Number(-1),
+            }
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// count function (non-overlapping substrings in a string, or equal items in a list)
+pub fn std_count(args: &[Value]) -> Value {
+    match (args.first(), args.get(1)) {
+        (Some(Value::String(s)), Some(Value::String(substr))) => {
+            if substr.is_empty() {
+                Value::Number(s.chars().count() as i64 + 1)
+            } else {
+                Value::Number(s.matches(substr.as_str()).count() as i64)
+            }
+        }
+        (Some(Value::Array(arr)), Some(item)) => {
+            Value::Number(arr.iter().filter(|value| *value == item).count() as i64)
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// isdigit function
+pub fn std_isdigit(args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::String(s)) => Value::Boolean(!s.is_empty() && s.chars().all(char::is_numeric)),
+        _ => Value::Undefined,
+    }
+}
+
+// isalpha function
+pub fn std_isalpha(args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::String(s)) => {
+            Value::Boolean(!s.is_empty() && s.chars().all(char::is_alphabetic))
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// title function (capitalises the first letter of every word)
+pub fn std_title(args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::String(s)) => {
+            let mut result = String::with_capacity(s.len());
+            let mut previous_is_letter = false;
+            for c in s.chars() {
+                if previous_is_letter {
+                    result.extend(c.to_lowercase());
+                } else {
+                    result.extend(c.to_uppercase());
+                }
+                previous_is_letter = c.is_alphabetic();
+            }
+            Value::String(result)
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// capwords function (capitalizes each word and joins them with single spaces, like Python's
+// string.capwords)
+pub fn std_capwords(args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::String(s)) => {
+            let words: Vec<String> = s
+                .split_whitespace()
+                .map(|word| {
+                    let mut chars = word.chars();
+                    match chars.next() {
+                        Some(first) => first
+                            .to_uppercase()
+                            .chain(chars.flat_map(char::to_lowercase))
+                            .collect(),
+                        None => String::new(),
+                    }
+                })
+                .collect();
+            Value::String(words.join(" "))
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// center function (pads both sides to width, extra padding goes on the right)
+pub fn std_center(args: &[Value]) -> Value {
+    let fill = match args.get(2) {
+        Some(Value::String(fill)) if fill.chars().count() == 1 => fill.chars().next().unwrap(),
+        None => ' ',
+        Some(_) => return Value::Undefined,
+    };
+    match (args.first(), args.get(1)) {
+        (Some(Value::String(s)), Some(Value::Number(width))) => {
+            let len = s.chars().count();
+            let width = (*width).max(0) as usize;
+            if width <= len {
+                return Value::String(s.clone());
+            }
+            let padding = width - len;
+            // Python puts the odd character on the left when the string length is odd
+            let left = padding / 2 + (padding & width & 1);
+            let right = padding - left;
+            let fill = |n: usize| fill.to_string().repeat(n);
+            Value::String(format!("{}{}{}", fill(left), s, fill(right)))
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// zfill function (pads with zeros on the left, after any sign)
+pub fn std_zfill(args: &[Value]) -> Value {
+    match (args.first(), args.get(1)) {
+        (Some(Value::String(s)), Some(Value::Number(width))) => {
+            let len = s.chars().count();
+            let width = (*width).max(0) as usize;
+            if width <= len {
+                return Value::String(s.clone());
+            }
+            let zeros = "0".repeat(width - len);
+            match s.strip_prefix(['+', '-']) {
+                Some(rest) => Value::String(format!("{}{}{}", &s[..1], zeros, rest)),
+                None => Value::String(zeros + s),
+            }
+        }
+        _ => Value::Undefined,
+    }
+}
+
+// chr function (unicode code point to a one-character string)
+pub fn std_chr(args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::Number(n)) => u32::try_from(*n)
+            .ok()
+            .and_then(char::from_u32)
+            .map(|c| Value::String(c.to_string()))
+            .unwrap_or(Value::Undefined),
+        _ => Value::Undefined,
+    }
+}
+
+// ord function (one-character string to its unicode code point)
+pub fn std_ord(args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::String(s)) => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Value::Number(c as i64),
+                _ => Value::Undefined,
+            }
+        }
+        _ => Value::Undefined,
+    }
+}
+
+/// Converts a byte offset into a character index
+pub fn char_index(s: &str, byte_offset: usize) -> usize {
+    s[..byte_offset].chars().count()
+}
+
+/// Converts a character index into a byte offset, clamped to the end of the string
+pub fn byte_offset(s: &str, char_index: usize) -> usize {
+    s.char_indices()
+        .nth(char_index)
+        .map_or(s.len(), |(offset, _)| offset)
+}
+
+// Resolves a possibly negative start position against the string's length in characters
+fn char_position(s: Option<&Value>, n: i64) -> usize {
+    let len = match s {
+        Some(Value::String(s)) => s.chars().count() as i64,
+        _ => 0,
+    };
+    if n < 0 {
+        (len + n).max(0) as usize
+    } else {
+        n as usize
+    }
+}
+
+/// The string module: import string, then string.digits or string.capwords(s)
+pub fn string_module() -> NativeModule {
+    let lowercase = "abcdefghijklmnopqrstuvwxyz";
+    let uppercase = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let constant = |s: &str| Value::String(s.to_string());
+    vec![
+        ("ascii_lowercase", constant(lowercase)),
+        ("ascii_uppercase", constant(uppercase)),
+        (
+            "ascii_letters",
+            constant(&format!("{}{}", lowercase, uppercase)),
+        ),
+        ("digits", constant("0123456789")),
+        ("hexdigits", constant("0123456789abcdefABCDEF")),
+        ("octdigits", constant("01234567")),
+        (
+            "punctuation",
+            constant("!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~"),
+        ),
+        ("whitespace", constant(" \t\n\r\x0b\x0c")),
+        native("capwords", Builtin::Positional(std_capwords)),
+    ]
+}
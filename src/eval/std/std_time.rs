@@ -0,0 +1,473 @@
+// Python-like time and date functions for TungLang, in the time module
+// The time comes from the interpreter's clock, which tests can replace with a FakeClock
+use crate::stdlib::{native, Builtin, NativeModule, StdLib};
+use crate::value::{Keywords, Value};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, TimeDelta};
+use miette::Result;
+use std::cell::Cell;
+use std::fmt::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Where the time module gets the time from. Programs use the SystemClock; tests can give the
+/// interpreter a FakeClock instead, so that time.now() and time.sleep() are predictable.
+pub trait Clock {
+    /// The current local date and time
+    fn now(&self) -> NaiveDateTime;
+    /// Seconds since a fixed moment, which never go backwards
+    fn monotonic(&self) -> f64;
+    /// Waits for the given number of seconds, which must not be negative; a wait too long for
+    /// the clock is an OverflowError
+    fn sleep(&self, seconds: f64) -> Result<()>;
+}
+
+/// The computer's own clock
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+
+    fn monotonic(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    fn sleep(&self, seconds: f64) -> Result<()> {
+        let duration: Duration = Duration::try_from_secs_f64(seconds)
+            .map_err(|_| miette::miette!("OverflowError: sleep length is too large."))?;
+        thread::sleep(duration);
+        Ok(())
+    }
+}
+
+/// A clock that starts at a given time and only moves when slept on or advanced, so sleeping
+/// takes no real time
+pub struct FakeClock {
+    now: Cell<NaiveDateTime>,
+    elapsed: Cell<f64>,
+}
+
+impl FakeClock {
+    pub fn new(start: NaiveDateTime) -> Self {
+        FakeClock {
+            now: Cell::new(start),
+            elapsed: Cell::new(0.0),
+        }
+    }
+
+    /// Moves the clock forward by the given number of seconds, unless that would take it past
+    /// the last date there is
+    pub fn advance(&self, seconds: f64) -> Result<()> {
+        let micros: f64 = (seconds * 1e6).round();
+        let now: Option<NaiveDateTime> = if micros.abs() < i64::MAX as f64 {
+            self.now
+                .get()
+                .checked_add_signed(TimeDelta::microseconds(micros as i64))
+        } else {
+            None
+        };
+        let now: NaiveDateTime =
+            now.ok_or_else(|| miette::miette!("OverflowError: date value out of range."))?;
+        self.now.set(now);
+        self.elapsed.set(self.elapsed.get() + seconds);
+        Ok(())
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> NaiveDateTime {
+        self.now.get()
+    }
+
+    fn monotonic(&self) -> f64 {
+        self.elapsed.get()
+    }
+
+    fn sleep(&self, seconds: f64) -> Result<()> {
+        self.advance(seconds)
+    }
+}
+
+/// The time module: import time, then time.now() or time.date(2025, 6, 1)
+pub fn time_module() -> NativeModule {
+    vec![
+        native("now", Builtin::Native(std_now, &[])),
+        native("today", Builtin::Native(std_today, &[])),
+        native("monotonic", Builtin::Native(std_monotonic, &[])),
+        native("sleep", Builtin::Native(std_sleep, &[])),
+        native("date", Builtin::Native(std_date, DATE_PARAMS)),
+        native("datetime", Builtin::Native(std_datetime, DATETIME_PARAMS)),
+        native("duration", Builtin::Native(std_duration, DURATION_PARAMS)),
+        native("parse_date", Builtin::Native(std_parse_date, &[])),
+        native("parse_datetime", Builtin::Native(std_parse_datetime, &[])),
+    ]
+}
+
+/// Methods of dates and datetimes: d.weekday(), d.strftime("%d/%m/%Y")
+pub fn date_methods() -> [(&'static str, Builtin); 4] {
+    [
+        ("weekday", Builtin::Native(std_weekday, &[])),
+        ("isoweekday", Builtin::Native(std_isoweekday, &[])),
+        ("strftime", Builtin::Native(std_strftime, &[])),
+        ("isoformat", Builtin::Native(std_isoformat, &[])),
+    ]
+}
+
+const DATE_PARAMS: &[&str] = &["year", "month", "day"];
+const DATETIME_PARAMS: &[&str] = &["year", "month", "day", "hour", "minute", "second"];
+const DURATION_PARAMS: &[&str] = &[
+    "days",
+    "seconds",
+    "microseconds",
+    "milliseconds",
+    "minutes",
+    "hours",
+    "weeks",
+];
+
+// now function (the current local date and time)
+fn std_now(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    no_arguments("now", args)?;
+    Ok(Value::DateTime(stdlib.clock().now()))
+}
+
+// today function (the current local date)
+fn std_today(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    no_arguments("today", args)?;
+    Ok(Value::Date(stdlib.clock().now().date()))
+}
+
+// monotonic function (seconds since a fixed moment, for measuring how long something takes)
+fn std_monotonic(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    no_arguments("monotonic", args)?;
+    Ok(Value::Float(stdlib.clock().monotonic()))
+}
+
+// sleep function (waits for a number of seconds)
+fn std_sleep(args: &[Value], _keywords: &Keywords, stdlib: &StdLib) -> Result<Value> {
+    let seconds: f64 = match args {
+        [seconds] => to_float("sleep", seconds)?,
+        _ => return Err(arity_error("sleep", "exactly 1 argument", args.len())),
+    };
+    if !(seconds >= 0.0 && seconds.is_finite()) {
+        return Err(miette::miette!(
+            "ValueError: sleep length must be a non-negative number of seconds."
+        ));
+    }
+    stdlib.clock().sleep(seconds)?;
+    Ok(Value::Undefined)
+}
+
+// date function (date(year, month, day))
+fn std_date(args: &[Value], keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let values = arguments("date", DATE_PARAMS, args, keywords)?;
+    let parts: Vec<i64> = required_ints("date", DATE_PARAMS, &values)?;
+    make_date(parts[0], parts[1], parts[2]).map(Value::Date)
+}
+
+// datetime function (datetime(year, month, day, hour=0, minute=0, second=0))
+fn std_datetime(args: &[Value], keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let values = arguments("datetime", DATETIME_PARAMS, args, keywords)?;
+    let date: NaiveDate = {
+        let parts: Vec<i64> = required_ints("datetime", DATE_PARAMS, &values[..3])?;
+        make_date(parts[0], parts[1], parts[2])?
+    };
+    let mut time: [i64; 3] = [0; 3];
+    for (part, value) in time.iter_mut().zip(&values[3..]) {
+        if let Some(value) = value {
+            *part = to_int("datetime", value)?;
+        }
+    }
+    let [hour, minute, second] = time;
+    let valid = |n: i64| u32::try_from(n).ok();
+    match (valid(hour), valid(minute), valid(second)) {
+        (Some(h), Some(m), Some(s)) => date.and_hms_opt(h, m, s),
+        _ => None,
+    }
+    .map(Value::DateTime)
+    .ok_or_else(|| {
+        miette::miette!(
+            "ValueError: {:02}:{:02}:{:02} is not a valid time.",
+            hour,
+            minute,
+            second
+        )
+    })
+}
+
+// duration function (a length of time, like Python's timedelta: duration(days=1, hours=2))
+fn std_duration(args: &[Value], keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let values = arguments("duration", DURATION_PARAMS, args, keywords)?;
+    // Microseconds in each unit, in the order of DURATION_PARAMS
+    let units: [f64; 7] = [86_400e6, 1e6, 1.0, 1e3, 60e6, 3_600e6, 604_800e6];
+    let mut micros: f64 = 0.0;
+    for (value, unit) in values.iter().zip(units) {
+        if let Some(value) = value {
+            micros += to_float("duration", value)? * unit;
+        }
+    }
+    let micros: f64 = micros.round();
+    // i64::MAX as f64 rounds up to 2^63, which is already out of range
+    if !micros.is_finite() || micros.abs() >= i64::MAX as f64 {
+        return Err(miette::miette!("OverflowError: date value out of range."));
+    }
+    Ok(Value::Duration(TimeDelta::microseconds(micros as i64)))
+}
+
+// parse_date function (reads a date from text, written like 2025-06-01 unless a format
+// such as "%d/%m/%Y" is given)
+fn std_parse_date(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let (text, format) = text_and_format("parse_date", args)?;
+    let format: &str = format.unwrap_or("%Y-%m-%d");
+    NaiveDate::parse_from_str(text, format)
+        .map(Value::Date)
+        .map_err(|_| no_match(text, format))
+}
+
+// parse_datetime function (reads a date and time from text, written like 2025-06-01 09:30:00
+// or 2025-06-01T09:30 unless a format is given)
+fn std_parse_datetime(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let (text, format) = text_and_format("parse_datetime", args)?;
+    let formats: &[&str] = match format {
+        Some(ref format) => std::slice::from_ref(format),
+        None => &[
+            "%Y-%m-%d %H:%M:%S%.f",
+            "%Y-%m-%dT%H:%M:%S%.f",
+            "%Y-%m-%d %H:%M",
+            "%Y-%m-%dT%H:%M",
+        ],
+    };
+    formats
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .map(Value::DateTime)
+        .ok_or_else(|| no_match(text, formats[0]))
+}
+
+// weekday method (Monday is 0 and Sunday is 6)
+fn std_weekday(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let date: NaiveDate = receiver_date("weekday", args)?;
+    Ok(Value::Number(date.weekday().num_days_from_monday() as i64))
+}
+
+// isoweekday method (Monday is 1 and Sunday is 7)
+fn std_isoweekday(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let date: NaiveDate = receiver_date("isoweekday", args)?;
+    Ok(Value::Number(date.weekday().number_from_monday() as i64))
+}
+
+// strftime method (formats with codes such as %d, %B and %A, like Python's)
+fn std_strftime(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    let (datetime, format): (NaiveDateTime, &str) = match args {
+        [Value::Date(date), Value::String(format)] => (date.and_time(Default::default()), format),
+        [Value::DateTime(datetime), Value::String(format)] => (*datetime, format),
+        [_, other] => {
+            return Err(miette::miette!(
+                "TypeError: strftime() format must be a str, not {}.",
+                other.type_name()
+            ))
+        }
+        _ => {
+            return Err(arity_error(
+                "strftime",
+                "exactly 1 argument",
+                args.len().max(1) - 1,
+            ))
+        }
+    };
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    let mut text = String::new();
+    // Unknown codes, or ones such as %z that need a time zone, can't be written
+    if items.contains(&Item::Error)
+        || write!(text, "{}", datetime.format_with_items(items.into_iter())).is_err()
+    {
+        return Err(miette::miette!(
+            "ValueError: Invalid format string '{}'.",
+            format
+        ));
+    }
+    Ok(Value::String(text))
+}
+
+// isoformat method (2025-06-01, or 2025-06-01T09:30:00 for a datetime)
+fn std_isoformat(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    match args {
+        [date @ Value::Date(_)] => Ok(Value::String(date.to_string())),
+        [Value::DateTime(datetime)] => Ok(Value::String(
+            Value::DateTime(*datetime).to_string().replacen(' ', "T", 1),
+        )),
+        _ => Err(arity_error(
+            "isoformat",
+            "no arguments",
+            args.len().max(1) - 1,
+        )),
+    }
+}
+
+// date method of a datetime (the date part on its own)
+pub fn std_date_part(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    match args {
+        [Value::DateTime(datetime)] => Ok(Value::Date(datetime.date())),
+        _ => Err(arity_error("date", "no arguments", args.len().max(1) - 1)),
+    }
+}
+
+// total_seconds method of a duration
+pub fn std_total_seconds(args: &[Value], _keywords: &Keywords, _stdlib: &StdLib) -> Result<Value> {
+    match args {
+        [Value::Duration(duration)] => Ok(Value::Float(duration.as_seconds_f64())),
+        _ => Err(arity_error(
+            "total_seconds",
+            "no arguments",
+            args.len().max(1) - 1,
+        )),
+    }
+}
+
+fn receiver_date(name: &str, args: &[Value]) -> Result<NaiveDate> {
+    match args {
+        [Value::Date(date)] => Ok(*date),
+        [Value::DateTime(datetime)] => Ok(datetime.date()),
+        _ => Err(arity_error(name, "no arguments", args.len().max(1) - 1)),
+    }
+}
+
+fn make_date(year: i64, month: i64, day: i64) -> Result<NaiveDate> {
+    let date = match (
+        i32::try_from(year),
+        u32::try_from(month),
+        u32::try_from(day),
+    ) {
+        (Ok(y), Ok(m), Ok(d)) => NaiveDate::from_ymd_opt(y, m, d),
+        _ => None,
+    };
+    date.ok_or_else(|| {
+        miette::miette!(
+            "ValueError: {}-{:02}-{:02} is not a valid date.",
+            year,
+            month,
+            day
+        )
+    })
+}
+
+/// Puts positional and keyword arguments in the order of `params`, with None for the ones
+/// not given. Builtin::Native has already rejected unknown keywords.
+fn arguments<'a>(
+    name: &str,
+    params: &[&str],
+    args: &'a [Value],
+    keywords: &'a Keywords,
+) -> Result<Vec<Option<&'a Value>>> {
+    if args.len() > params.len() {
+        return Err(miette::miette!(
+            "Error: {}() takes at most {} arguments, got {}.",
+            name,
+            params.len(),
+            args.len()
+        ));
+    }
+    let mut values: Vec<Option<&Value>> = vec![None; params.len()];
+    for (slot, arg) in values.iter_mut().zip(args) {
+        *slot = Some(arg);
+    }
+    for (keyword, value) in keywords {
+        let position: usize = params.iter().position(|p| p == keyword).unwrap();
+        if values[position].is_some() {
+            return Err(miette::miette!(
+                "Error: {}() got multiple values for argument '{}'.",
+                name,
+                keyword
+            ));
+        }
+        values[position] = Some(value);
+    }
+    Ok(values)
+}
+
+fn required_ints(name: &str, params: &[&str], values: &[Option<&Value>]) -> Result<Vec<i64>> {
+    params
+        .iter()
+        .zip(values)
+        .map(|(param, value)| match value {
+            Some(value) => to_int(name, value),
+            None => Err(miette::miette!(
+                "Error: {}() missing required argument '{}'.",
+                name,
+                param
+            )),
+        })
+        .collect()
+}
+
+fn text_and_format<'a>(name: &str, args: &'a [Value]) -> Result<(&'a str, Option<&'a str>)> {
+    match args {
+        [Value::String(text)] => Ok((text, None)),
+        [Value::String(text), Value::String(format)] => Ok((text, Some(format))),
+        [_] | [_, _] => Err(miette::miette!(
+            "TypeError: {}() arguments must be strings.",
+            name
+        )),
+        _ => Err(arity_error(name, "1 or 2 arguments", args.len())),
+    }
+}
+
+fn no_match(text: &str, format: &str) -> miette::Report {
+    miette::miette!(
+        "ValueError: time data '{}' does not match format '{}'.",
+        text,
+        format
+    )
+}
+
+fn to_int(name: &str, value: &Value) -> Result<i64> {
+    match value {
+        Value::Number(n) => Ok(*n),
+        other => Err(miette::miette!(
+            "TypeError: {}() argument must be an int, not {}.",
+            name,
+            other.type_name()
+        )),
+    }
+}
+
+fn to_float(name: &str, value: &Value) -> Result<f64> {
+    match value {
+        Value::Number(n) => Ok(*n as f64),
+        Value::Float(f) => Ok(*f),
+        other => Err(miette::miette!(
+            "TypeError: {}() argument must be a number, not {}.",
+            name,
+            other.type_name()
+        )),
+    }
+}
+
+fn no_arguments(name: &str, args: &[Value]) -> Result<()> {
+    match args.len() {
+        0 => Ok(()),
+        n => Err(arity_error(name, "no arguments", n)),
+    }
+}
+
+fn arity_error(name: &str, expected: &str, got: usize) -> miette::Report {
+    miette::miette!("Error: {}() takes {}, got {}.", name, expected, got)
+}
@@ -0,0 +1,173 @@
+// Decodes TungLang string literals: quotes, raw prefixes and escape sequences
+use pest::iterators::Pair;
+
+use crate::parser::Rule;
+
+/// An invalid escape sequence, with its byte range relative to the literal text
+#[derive(Debug, Clone, PartialEq)]
+pub struct EscapeError {
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+/// Decodes a `string` pair into its value, reporting bad escapes against the source span
+pub fn decode_string_pair(pair: &Pair<Rule>) -> miette::Result<String> {
+    decode_string_literal(pair.as_str()).map_err(|e| escape_report(pair, e))
+}
+
+/// Decodes the text between replacement fields of an f-string, keeping backslashes as
+/// written when the f-string is raw
+pub fn decode_fstring_text(pair: &Pair<Rule>, raw: bool) -> miette::Result<String> {
+    // Escapes are decoded before doubled braces are collapsed so errors point at the right column
+    let text = pair.as_str();
+    if raw {
+        return Ok(text.replace("{{", "{").replace("}}", "}"));
+    }
+    match decode_escapes(text) {
+        Ok(decoded) => Ok(decoded.replace("{{", "{").replace("}}", "}")),
+        Err(e) => Err(escape_report(pair, e)),
+    }
+}
+
+/// Strips the raw prefix and the quotes from a string literal and decodes its escapes.
+/// Handles "double", 'single', """triple""" and r"raw" literals.
+pub fn decode_string_literal(literal: &str) -> Result<String, EscapeError> {
+    let (raw, quoted) = match literal.strip_prefix('r') {
+        Some(quoted) => (true, quoted),
+        None => (false, literal),
+    };
+    let quote_len = if quoted.starts_with("\"\"\"") || quoted.starts_with("'''") {
+        3
+    } else {
+        1
+    };
+    let body = &quoted[quote_len..quoted.len() - quote_len];
+    if raw {
+        return Ok(body.to_string());
+    }
+    let body_offset = literal.len() - quoted.len() + quote_len;
+    decode_escapes(body).map_err(|e| EscapeError {
+        start: e.start + body_offset,
+        end: e.end + body_offset,
+        message: e.message,
+    })
+}
+
+/// Replaces backslash escapes with the characters they stand for
+pub fn decode_escapes(text: &str) -> Result<String, EscapeError> {
+    let mut decoded = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        let error = |end: usize, message: String| EscapeError {
+            start,
+            end,
+            message,
+        };
+        let (escape_pos, escape) = match chars.next() {
+            Some(next) => next,
+            None => {
+                return Err(error(
+                    text.len(),
+                    "unterminated escape sequence".to_string(),
+                ))
+            }
+        };
+        let end = escape_pos + escape.len_utf8();
+        match escape {
+            'n' => decoded.push('\n'),
+            't' => decoded.push('\t'),
+            'r' => decoded.push('\r'),
+            '0' => decoded.push('\0'),
+            'a' => decoded.push('\x07'),
+            'b' => decoded.push('\x08'),
+            'f' => decoded.push('\x0c'),
+            'v' => decoded.push('\x0b'),
+            '\\' => decoded.push('\\'),
+            '"' => decoded.push('"'),
+            '\'' => decoded.push('\''),
+            // A backslash at the end of a line joins it with the next one
+            '\n' => {}
+            '\r' => {
+                if let Some((_, '\n')) = chars.peek() {
+                    chars.next();
+                }
+            }
+            'x' | 'u' => {
+                let braced = escape == 'u' && chars.peek().map(|(_, c)| *c) == Some('{');
+                if braced {
+                    chars.next();
+                }
+                let mut digits = String::new();
+                let mut end = end;
+                let mut closed = false;
+                while let Some((pos, c)) = chars.peek().copied() {
+                    if braced && c == '}' {
+                        chars.next();
+                        end = pos + 1;
+                        closed = true;
+                        break;
+                    }
+                    if !c.is_ascii_hexdigit() || (!braced && digits.len() == expected_len(escape)) {
+                        break;
+                    }
+                    digits.push(c);
+                    chars.next();
+                    end = pos + 1;
+                }
+                let valid_len = if braced {
+                    closed && (1..=6).contains(&digits.len())
+                } else {
+                    digits.len() == expected_len(escape)
+                };
+                if !valid_len {
+                    let expected = match escape {
+                        'x' => "\\xHH",
+                        _ => "\\u{HHHHHH} or \\uHHHH",
+                    };
+                    return Err(error(
+                        end,
+                        format!("malformed escape, expected {}", expected),
+                    ));
+                }
+                let code = u32::from_str_radix(&digits, 16).unwrap();
+                match char::from_u32(code) {
+                    Some(c) => decoded.push(c),
+                    None => {
+                        return Err(error(
+                            end,
+                            format!("'{:X}' is not a valid unicode character", code),
+                        ))
+                    }
+                }
+            }
+            other => {
+                return Err(error(end, format!("unknown escape sequence '\\{}'", other)));
+            }
+        }
+    }
+    Ok(decoded)
+}
+
+fn expected_len(escape: char) -> usize {
+    if escape == 'x' {
+        2
+    } else {
+        4
+    }
+}
+
+fn escape_report(pair: &Pair<Rule>, e: EscapeError) -> miette::Report {
+    let offset = pair.as_span().start();
+    miette::miette!(
+        labels = vec![miette::LabeledSpan::at(
+            offset + e.start..offset + e.end,
+            e.message
+        )],
+        "Error: Invalid escape sequence in string literal."
+    )
+}
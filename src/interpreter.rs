@@ -1,134 +1,975 @@
-use crate::eval::evaluate_expression::evaluate_expression;
-use crate::parser::Rule;
+use crate::environment::{Env, Environment};
+use crate::eval::evaluate_expression::{evaluate_arguments, evaluate_expression};
+use crate::eval::operators::{
+    assign_index, delete_index, get_attribute, no_attribute, set_attribute,
+};
+use crate::eval::pattern::{check_exhaustive, match_pattern, Bindings};
+use crate::eval::std::std_file::read_lines;
+use crate::eval::string_literal::{decode_fstring_text, decode_string_pair};
+use crate::parser::{Rule, TungParser};
+use crate::preprocess::preprocess_code;
 use crate::stdlib::StdLib;
-use crate::value::Value;
+use crate::value::{Class, Enum, File, Function, Instance, Keywords, Module, Value};
+use indexmap::IndexMap;
+use miette::NamedSource;
 use pest::iterators::{Pair, Pairs};
+use pest::Parser;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-pub fn run_program(parsed: Pairs<Rule>) -> miette::Result<()> {
-    let mut variables: HashMap<String, Value> = HashMap::new();
-    let stdlib: StdLib = StdLib::new();
-    for pair in parsed {
-        execute_statement(pair, &mut variables, &stdlib)?;
+/// A function made with def, lambda or fn. Default values are evaluated once, when the
+/// function is made.
+pub struct UserFunction {
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    /// A block of statements, or the expression a lambda returns
+    pub body: Pair<'static, Rule>,
+    /// The scope the function was made in, which its body can use
+    pub closure: Env,
+    /// The name and file of the program or module the function was made in, so errors in it
+    /// can say where they happened when it is called from another file
+    pub module: String,
+    pub file: String,
+}
+
+pub enum Parameter {
+    /// A named parameter and its default value, if any. Named parameters after *args can
+    /// only be passed by keyword.
+    Named(String, Option<Value>),
+    /// *args: extra positional arguments, as a list
+    VarArgs(String),
+    /// **kwargs: extra keyword arguments, as a dict
+    KwArgs(String),
+}
+
+impl Parameter {
+    fn name(&self) -> &str {
+        match self {
+            Parameter::Named(name, _) | Parameter::VarArgs(name) | Parameter::KwArgs(name) => name,
+        }
+    }
+}
+
+/// How deeply user functions may call each other, like Python's recursion limit
+const MAX_CALL_DEPTH: usize = 1000;
+
+/// What to do after a statement has run
+enum Flow {
+    /// Carry on with the next statement
+    Next,
+    /// A return statement ran: stop and hand this value back to the caller
+    Return(Value),
+}
+
+/// Runs the program read from `file`, which modules it imports are looked for next to
+pub fn run_program(
+    parsed: Pairs<'static, Rule>,
+    file: &Path,
+    stdlib: &StdLib,
+) -> miette::Result<()> {
+    check_string_literals(parsed.clone())?;
+    let env: Env = module_globals("__main__", file);
+    stdlib.set_running_file(file.display().to_string());
+    // Importing the program itself from one of its modules is a cycle too
+    let key: PathBuf = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+    stdlib.start_import(&key)?;
+    let result = execute_statements(parsed, &env, stdlib);
+    stdlib.finish_import();
+    if let Flow::Return(_) = result? {
+        return Err(miette::miette!("Error: 'return' outside function."));
+    }
+    Ok(())
+}
+
+/// The global scope of a program or module, which knows its name and file
+fn module_globals(name: &str, file: &Path) -> Env {
+    let globals: Env = Environment::global();
+    globals.define("__name__".to_string(), Value::String(name.to_string()));
+    globals.define(
+        "__file__".to_string(),
+        Value::String(file.display().to_string()),
+    );
+    globals
+}
+
+/// Finds and loads the module `name`: one that comes with TungLang such as math, or else a file
+/// (games.dice is games/dice.tung), looking next to the file that imports it first and then in
+/// the module path. A module only runs the first time it is imported; later imports share the
+/// same module.
+fn import_module(name: &str, env: &Env, stdlib: &StdLib) -> miette::Result<Rc<Module>> {
+    if let Some(module) = stdlib.native_module(name) {
+        return Ok(module);
+    }
+    let relative: PathBuf = PathBuf::from(format!("{}.tung", name.replace('.', "/")));
+    let importer_dir: PathBuf = match env.get("__file__") {
+        Some(Value::String(importer)) => Path::new(&importer)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+        _ => PathBuf::new(),
+    };
+    let file: PathBuf = std::iter::once(importer_dir)
+        .chain(stdlib.module_path().iter().cloned())
+        .map(|dir| dir.join(&relative))
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| miette::miette!("ImportError: No module named '{}'.", name))?;
+    let file: PathBuf = fs::canonicalize(&file).unwrap_or(file);
+    if let Some(module) = stdlib.loaded_module(&file) {
+        return Ok(module);
+    }
+    stdlib.start_import(&file)?;
+    let result = load_module(name, &file, stdlib);
+    stdlib.finish_import();
+    let module: Rc<Module> = result?;
+    stdlib.add_loaded_module(file, Rc::clone(&module));
+    Ok(module)
+}
+
+/// Parses and runs a module's file in a global scope of its own
+fn load_module(name: &str, file: &Path, stdlib: &StdLib) -> miette::Result<Rc<Module>> {
+    let path: String = file.display().to_string();
+    let source: String = fs::read_to_string(file).map_err(|e| {
+        miette::miette!(
+            "ImportError: Can't read module '{}' ({}): {}",
+            name,
+            path,
+            e
+        )
+    })?;
+    // Like the program's, the module's source is kept for the whole run
+    let source: &'static str = Box::leak(preprocess_code(&source).into_boxed_str());
+    let parsed: Pairs<Rule> = match TungParser::parse(Rule::program, source) {
+        Ok(mut pairs) => pairs.next().unwrap().into_inner(),
+        Err(e) => {
+            return Err(miette::miette!(
+                "Error parsing module '{}': {}",
+                name,
+                e.with_path(&path)
+            ));
+        }
+    };
+    check_string_literals(parsed.clone())
+        .map_err(|e| e.with_source_code(NamedSource::new(&path, source)))?;
+    let globals: Env = module_globals(name, file);
+    let importer_file: String = stdlib.set_running_file(path.clone());
+    let result = execute_statements(parsed, &globals, stdlib);
+    stdlib.set_running_file(importer_file);
+    match result {
+        Ok(Flow::Next) => Ok(Rc::new(Module {
+            name: name.to_string(),
+            globals,
+        })),
+        Ok(Flow::Return(_)) => Err(miette::miette!(
+            "Error: 'return' outside function in module '{}' ({}).",
+            name,
+            path
+        )),
+        Err(e) => Err(e.wrap_err(format!("Error in module '{}' ({})", name, path))),
+    }
+}
+
+/// Calls a value, which must be a function, or a class to make an instance of
+pub fn call_value(
+    callee: &Value,
+    args: Vec<Value>,
+    keywords: Keywords,
+    stdlib: &StdLib,
+) -> miette::Result<Value> {
+    match callee {
+        Value::Function(Function::Builtin(name, builtin)) => {
+            builtin.call(name, args, keywords, stdlib)
+        }
+        Value::Function(Function::User(function)) => {
+            call_user_function(function, args, keywords, stdlib)
+        }
+        Value::Function(Function::Bound(instance, function)) => {
+            let mut args = args;
+            args.insert(0, Value::Instance(Rc::clone(instance)));
+            call_user_function(function, args, keywords, stdlib)
+        }
+        Value::Class(class) => instantiate(class, args, keywords, stdlib),
+        Value::Enum(enum_type) => match (args.as_slice(), keywords.is_empty()) {
+            // Currency(1) finds the variant with that value
+            ([value], true) => match enum_type.variants.iter().position(|(_, v)| v == value) {
+                Some(index) => Ok(Value::Variant(Rc::clone(enum_type), index)),
+                None => Err(miette::miette!(
+                    "ValueError: {} is not a valid {}.",
+                    value.repr(),
+                    enum_type.name
+                )),
+            },
+            _ => Err(miette::miette!(
+                "Error: {}() takes exactly 1 argument, got {}.",
+                enum_type.name,
+                args.len() + keywords.len()
+            )),
+        },
+        other => Err(miette::miette!(
+            "Error: '{}' object is not callable.",
+            other.type_name()
+        )),
+    }
+}
+
+/// Makes a new instance of a class and runs its init method, if it has one, to set it up
+fn instantiate(
+    class: &Rc<Class>,
+    mut args: Vec<Value>,
+    keywords: Keywords,
+    stdlib: &StdLib,
+) -> miette::Result<Value> {
+    let instance = Rc::new(Instance {
+        class: Rc::clone(class),
+        fields: RefCell::new(IndexMap::new()),
+    });
+    match class.find_method("init") {
+        Some(init) => {
+            args.insert(0, Value::Instance(Rc::clone(&instance)));
+            call_user_function(&init, args, keywords, stdlib)?;
+        }
+        None if !args.is_empty() || !keywords.is_empty() => {
+            return Err(miette::miette!(
+                "Error: {}() takes no arguments.",
+                class.name
+            ))
+        }
+        None => {}
+    }
+    Ok(Value::Instance(instance))
+}
+
+/// Calls a function made with def, lambda or fn. The body runs in a new scope inside the
+/// one the function was made in.
+pub fn call_user_function(
+    function: &UserFunction,
+    args: Vec<Value>,
+    keywords: Keywords,
+    stdlib: &StdLib,
+) -> miette::Result<Value> {
+    let scope: Env = Environment::child(&function.closure);
+    for (name, value) in bind_arguments(function, args, keywords)? {
+        scope.define(name, value);
+    }
+    if stdlib.adjust_call_depth(1) > MAX_CALL_DEPTH {
+        stdlib.adjust_call_depth(-1);
+        return Err(miette::miette!(
+            "Error: Maximum recursion depth exceeded in '{}'.",
+            function.name
+        ));
+    }
+    // A function from another file runs as part of that file, and its errors say so
+    if function.file == stdlib.running_file() {
+        let result = execute_function_body(function, &scope, stdlib);
+        stdlib.adjust_call_depth(-1);
+        return result;
+    }
+    let caller_file: String = stdlib.set_running_file(function.file.clone());
+    let result = execute_function_body(function, &scope, stdlib);
+    stdlib.set_running_file(caller_file);
+    stdlib.adjust_call_depth(-1);
+    result.map_err(|e| {
+        e.wrap_err(format!(
+            "Error in {}() in module '{}' ({})",
+            function.name, function.module, function.file
+        ))
+    })
+}
+
+fn execute_function_body(
+    function: &UserFunction,
+    scope: &Env,
+    stdlib: &StdLib,
+) -> miette::Result<Value> {
+    let body: Pair<'static, Rule> = function.body.clone();
+    if body.as_rule() != Rule::block {
+        return evaluate_expression(body, scope, stdlib);
+    }
+    match execute_statements(body.into_inner(), scope, stdlib)? {
+        Flow::Return(value) => Ok(value),
+        Flow::Next => Ok(Value::Undefined),
+    }
+}
+
+/// Matches call arguments to parameters the way Python does
+fn bind_arguments(
+    function: &UserFunction,
+    args: Vec<Value>,
+    keywords: Keywords,
+) -> miette::Result<HashMap<String, Value>> {
+    let name: &str = &function.name;
+    let mut bound: HashMap<String, Value> = HashMap::new();
+    let mut args = args.into_iter();
+    let mut positional_count: usize = 0;
+    let mut accepts_varargs = false;
+    for parameter in &function.parameters {
+        match parameter {
+            Parameter::Named(param, _) if !accepts_varargs => {
+                positional_count += 1;
+                if let Some(value) = args.next() {
+                    bound.insert(param.clone(), value);
+                }
+            }
+            Parameter::VarArgs(param) => {
+                accepts_varargs = true;
+                bound.insert(param.clone(), Value::Array(args.by_ref().collect()));
+            }
+            _ => {}
+        }
+    }
+    let extra: usize = args.len();
+    if extra > 0 {
+        return Err(miette::miette!(
+            "Error: {}() takes {} positional arguments but {} were given.",
+            name,
+            positional_count,
+            positional_count + extra
+        ));
+    }
+    let kwargs: Option<&String> =
+        function
+            .parameters
+            .iter()
+            .find_map(|parameter| match parameter {
+                Parameter::KwArgs(param) => Some(param),
+                _ => None,
+            });
+    let mut extra_keywords: IndexMap<Value, Value> = IndexMap::new();
+    for (keyword, value) in keywords {
+        let is_named = function
+            .parameters
+            .iter()
+            .any(|parameter| matches!(parameter, Parameter::Named(param, _) if *param == keyword));
+        if is_named {
+            if bound.contains_key(&keyword) {
+                return Err(miette::miette!(
+                    "Error: {}() got multiple values for argument '{}'.",
+                    name,
+                    keyword
+                ));
+            }
+            bound.insert(keyword, value);
+        } else if kwargs.is_some() {
+            extra_keywords.insert(Value::String(keyword), value);
+        } else {
+            return Err(miette::miette!(
+                "Error: {}() got an unexpected keyword argument '{}'.",
+                name,
+                keyword
+            ));
+        }
+    }
+    if let Some(param) = kwargs {
+        bound.insert(param.clone(), Value::Dict(extra_keywords));
+    }
+    for parameter in &function.parameters {
+        if let Parameter::Named(param, default) = parameter {
+            if bound.contains_key(param) {
+                continue;
+            }
+            match default {
+                Some(value) => {
+                    bound.insert(param.clone(), value.clone());
+                }
+                None => {
+                    return Err(miette::miette!(
+                        "Error: {}() missing required argument '{}'.",
+                        name,
+                        param
+                    ))
+                }
+            }
+        }
+    }
+    Ok(bound)
+}
+
+/// Builds a function from its parameters followed by its body, checking the parameter list
+/// and evaluating defaults
+pub fn make_function(
+    name: String,
+    parts: Pairs<'static, Rule>,
+    env: &Env,
+    stdlib: &StdLib,
+) -> miette::Result<Value> {
+    let mut parameters: Vec<Parameter> = Vec::new();
+    let mut body: Option<Pair<'static, Rule>> = None;
+    let mut seen_default = false;
+    let mut seen_varargs = false;
+    for part in parts {
+        let parameter: Parameter = match part.as_rule() {
+            Rule::parameter => {
+                let mut param_inner: Pairs<Rule> = part.into_inner();
+                let param: String = param_inner.next().unwrap().as_str().to_string();
+                let default: Option<Value> = match param_inner.next() {
+                    Some(expr) => Some(evaluate_expression(expr, env, stdlib)?),
+                    None => None,
+                };
+                if default.is_some() {
+                    seen_default = true;
+                } else if seen_default && !seen_varargs {
+                    return Err(miette::miette!(
+                        "Error: Parameter '{}' without a default follows a parameter with one in function '{}'.",
+                        param,
+                        name
+                    ));
+                }
+                Parameter::Named(param, default)
+            }
+            Rule::varargs_parameter => {
+                if seen_varargs {
+                    return Err(miette::miette!(
+                        "Error: Function '{}' can only have one *args parameter.",
+                        name
+                    ));
+                }
+                seen_varargs = true;
+                Parameter::VarArgs(part.into_inner().next().unwrap().as_str().to_string())
+            }
+            Rule::kwargs_parameter => {
+                Parameter::KwArgs(part.into_inner().next().unwrap().as_str().to_string())
+            }
+            _ => {
+                body = Some(part);
+                continue;
+            }
+        };
+        if let Some(Parameter::KwArgs(_)) = parameters.last() {
+            return Err(miette::miette!(
+                "Error: **kwargs must be the last parameter of function '{}'.",
+                name
+            ));
+        }
+        if parameters.iter().any(|p| p.name() == parameter.name()) {
+            return Err(miette::miette!(
+                "Error: Duplicate parameter '{}' in function '{}'.",
+                parameter.name(),
+                name
+            ));
+        }
+        parameters.push(parameter);
+    }
+    let global_text = |name: &str| match env.get(name) {
+        Some(Value::String(text)) => text,
+        _ => String::new(),
+    };
+    Ok(Value::Function(Function::User(Rc::new(UserFunction {
+        name,
+        parameters,
+        body: body.unwrap(),
+        closure: Rc::clone(env),
+        module: global_text("__name__"),
+        file: global_text("__file__"),
+    }))))
+}
+
+/// Reports invalid escape sequences up front, before any statement has run
+fn check_string_literals(parsed: Pairs<Rule>) -> miette::Result<()> {
+    for pair in parsed.flatten() {
+        match pair.as_rule() {
+            Rule::string => {
+                decode_string_pair(&pair)?;
+            }
+            Rule::fstring => {
+                // Only the f-string's own text; fields are visited on their own
+                let mut raw: bool = false;
+                for part in pair.into_inner() {
+                    match part.as_rule() {
+                        Rule::fstring_prefix => raw = part.as_str() != "f",
+                        Rule::fstring_text => {
+                            decode_fstring_text(&part, raw)?;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
     }
     Ok(())
 }
 
 fn execute_statement(
-    pair: Pair<Rule>,
-    variables: &mut HashMap<String, Value>,
+    pair: Pair<'static, Rule>,
+    env: &Env,
     stdlib: &StdLib,
-) -> miette::Result<()> {
+) -> miette::Result<Flow> {
     match pair.as_rule() {
         Rule::variable_declaration => {
             let mut inner: Pairs<Rule> = pair.into_inner();
-            let var_name: String = inner.next().unwrap().as_str().to_string();
-            let value: Value = evaluate_expression(inner.next().unwrap(), variables, stdlib)?;
-            variables.insert(var_name, value);
+            let target: Pair<Rule> = inner.next().unwrap();
+            let value: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+            define_target(target, value, env)?;
         }
         Rule::assignment => {
             let mut inner: Pairs<Rule> = pair.into_inner();
-            let var_name: String = inner.next().unwrap().as_str().to_string();
-            let value: Value = evaluate_expression(inner.next().unwrap(), variables, stdlib)?;
-            if variables.contains_key(&var_name) {
-                variables.insert(var_name, value);
-            } else {
-                return Err(miette::miette!(
-                    "Assignment to undefined variable '{}'.",
-                    var_name
-                ));
+            let target: Pair<Rule> = inner.next().unwrap();
+            let value: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+            bind_target(target, value, &mut |var_name, value| {
+                if env.assign(var_name, value) {
+                    Ok(())
+                } else {
+                    Err(miette::miette!(
+                        "Assignment to undefined variable '{}'.",
+                        var_name
+                    ))
+                }
+            })?;
+        }
+        Rule::attribute_assignment => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let target: Pair<Rule> = inner.next().unwrap();
+            let value: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+            let (owner, name) = attribute_owner(target, env)?;
+            set_attribute(&owner, name, value)?;
+        }
+        Rule::index_assignment => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let target: Pair<Rule> = inner.next().unwrap();
+            let var_name: &str = target.as_str();
+            let index: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+            let value: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+            match with_target(target, env, |container| {
+                assign_index(container, index, value)
+            })? {
+                Some(result) => result?,
+                None => {
+                    return Err(miette::miette!(
+                        "Assignment to undefined variable '{}'.",
+                        var_name
+                    ))
+                }
+            }
+        }
+        Rule::del_statement => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let target: Pair<Rule> = inner.next().unwrap();
+            let var_name: &str = target.as_str();
+            let index: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+            match with_target(target, env, |container| delete_index(container, &index))? {
+                Some(result) => result?,
+                None => {
+                    return Err(miette::miette!(
+                        "Error: Variable '{}' is not defined.",
+                        var_name
+                    ))
+                }
             }
         }
         Rule::augmented_assignment => {
             let mut inner: Pairs<Rule> = pair.into_inner();
-            let var_name: String = inner.next().unwrap().as_str().to_string();
+            let target: Pair<Rule> = inner.next().unwrap();
             let op_pair: Pair<Rule> = inner.next().unwrap();
             let op: &str = op_pair.as_str();
-            let value: Value = evaluate_expression(inner.next().unwrap(), variables, stdlib)?;
-            if let Some(current) = variables.get(&var_name).cloned() {
+            let value: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+            if let Some(current) = with_target(target.clone(), env, |slot| slot.clone())? {
                 let op_str: &str = &op[..op.len() - 1];
                 let new_value: Value =
                     crate::eval::operators::apply_operator(current, value, op_str)?;
-                variables.insert(var_name, new_value);
+                with_target(target, env, |slot| *slot = new_value)?;
             } else {
                 return Err(miette::miette!(
                     "Assignment to undefined variable '{}'.",
-                    var_name
+                    target.as_str()
                 ));
             }
         }
         Rule::print_statement => {
-            let mut inner: Pairs<Rule> = pair.into_inner();
-            let value: Value = evaluate_expression(inner.next().unwrap(), variables, stdlib)?;
-            match value {
-                Value::String(s) => println!("{}", s),
-                Value::Number(n) => println!("{}", n),
-                Value::Float(f) => println!("{}", f),
-                Value::Boolean(b) => println!("{}", b),
-                Value::Array(arr) => println!("{:?}", arr),
-                Value::Dict(map) => println!("{:?}", map),
-                Value::Undefined => {
-                    return Err(miette::miette!("Attempted to print an undefined value."))
-                }
+            let (values, keywords) = evaluate_arguments("print", pair.into_inner(), env, stdlib)?;
+            if values.contains(&Value::Undefined) {
+                return Err(miette::miette!("Attempted to print an undefined value."));
             }
+            let print = stdlib.get("print").expect("print is always registered");
+            print.call("print", values, keywords, stdlib)?;
         }
         Rule::if_statement => {
-            execute_if_statement(pair, variables, stdlib)?;
+            return execute_if_statement(pair, env, stdlib);
         }
         Rule::while_statement => {
             let mut inner: Pairs<Rule> = pair.into_inner();
             let condition: Pair<Rule> = inner.next().unwrap();
             let block: Pair<Rule> = inner.next().unwrap();
             loop {
-                let cond_val = evaluate_expression(condition.clone(), variables, stdlib)?;
+                let cond_val = evaluate_expression(condition.clone(), env, stdlib)?;
                 if !is_truthy(cond_val.clone()) {
                     break;
                 }
-                let mut local_vars: HashMap<String, Value> = variables.clone();
-                execute_block(block.clone(), &mut local_vars, stdlib)?;
-                for (k, v) in local_vars.iter() {
-                    if variables.contains_key(k) {
-                        variables.insert(k.clone(), v.clone());
-                    }
+                if let Flow::Return(value) = execute_block(block.clone(), env, stdlib)? {
+                    return Ok(Flow::Return(value));
+                }
+            }
+        }
+        Rule::with_statement => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let value: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+            let file: Rc<File> = match &value {
+                Value::File(file) => Rc::clone(file),
+                other => {
+                    return Err(miette::miette!(
+                        "TypeError: '{}' object can't be used in a with statement.",
+                        other.type_name()
+                    ))
+                }
+            };
+            let scope: Env = Environment::child(env);
+            scope.define(inner.next().unwrap().as_str().to_string(), value);
+            let result = execute_statements(inner.next().unwrap().into_inner(), &scope, stdlib);
+            // The file is closed however the block ends, even by an error or a return
+            file.close();
+            if let Flow::Return(value) = result? {
+                return Ok(Flow::Return(value));
+            }
+        }
+        Rule::try_statement => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let body: Pair<Rule> = inner.next().unwrap();
+            let (name, handler): (Option<&str>, Pair<Rule>) = match (inner.next(), inner.next()) {
+                (Some(name), Some(handler)) => (Some(name.as_str()), handler),
+                (Some(handler), None) => (None, handler),
+                _ => unreachable!(),
+            };
+            let error: miette::Report = match execute_block(body, env, stdlib) {
+                Ok(Flow::Next) => return Ok(Flow::Next),
+                Ok(Flow::Return(value)) => return Ok(Flow::Return(value)),
+                Err(error) => error,
+            };
+            let scope: Env = Environment::child(env);
+            if let Some(name) = name {
+                // The innermost message is the error itself, without the module or function
+                // it was raised in: "JSONDecodeError: expected value at line 1 column 1."
+                let message: String = error
+                    .chain()
+                    .last()
+                    .map_or_else(|| error.to_string(), |cause| cause.to_string());
+                scope.define(name.to_string(), Value::String(message));
+            }
+            if let Flow::Return(value) = execute_statements(handler.into_inner(), &scope, stdlib)? {
+                return Ok(Flow::Return(value));
+            }
+        }
+        Rule::for_statement => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let target: Pair<Rule> = inner.next().unwrap();
+            let iterable: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+            let block: Pair<Rule> = inner.next().unwrap();
+            for item in iterate(iterable)? {
+                // Each iteration gets its own loop variable, so functions made in the loop
+                // keep the item they saw
+                let scope: Env = Environment::child(env);
+                define_target(target.clone(), item, &scope)?;
+                if let Flow::Return(value) =
+                    execute_statements(block.clone().into_inner(), &scope, stdlib)?
+                {
+                    return Ok(Flow::Return(value));
+                }
+            }
+        }
+        Rule::class_definition => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let name: &'static str = inner.next().unwrap().as_str();
+            let mut parent: Option<Rc<Class>> = None;
+            let mut methods: IndexMap<String, Rc<UserFunction>> = IndexMap::new();
+            for part in inner {
+                if part.as_rule() == Rule::IDENTIFIER {
+                    let builtin = || stdlib.lookup(part.as_str()).map(Value::Function);
+                    parent = match env.get(part.as_str()).or_else(builtin) {
+                        Some(Value::Class(class)) => Some(class),
+                        Some(other) => {
+                            return Err(miette::miette!(
+                                "Error: Class '{}' can only inherit from a class, not {}.",
+                                name,
+                                other.type_name()
+                            ))
+                        }
+                        None => {
+                            return Err(miette::miette!(
+                                "Error: Class '{}' is not defined.",
+                                part.as_str()
+                            ))
+                        }
+                    };
+                    continue;
+                }
+                let mut definition: Pairs<Rule> = part.into_inner();
+                let method_name: String = definition.next().unwrap().as_str().to_string();
+                if let Value::Function(Function::User(method)) =
+                    make_function(method_name.clone(), definition, env, stdlib)?
+                {
+                    methods.insert(method_name, method);
+                }
+            }
+            let class = Class {
+                name,
+                parent,
+                methods,
+            };
+            env.define(name.to_string(), Value::Class(Rc::new(class)));
+        }
+        Rule::import_statement => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let name: &str = inner.next().unwrap().as_str();
+            let module: Rc<Module> = import_module(name, env, stdlib)?;
+            // import games.dice makes a variable called dice
+            let variable: &str = match inner.next() {
+                Some(alias) => alias.as_str(),
+                None => name.rsplit('.').next().unwrap(),
+            };
+            env.define(variable.to_string(), Value::Module(module));
+        }
+        Rule::from_import_statement => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let name: &str = inner.next().unwrap().as_str();
+            let module: Rc<Module> = import_module(name, env, stdlib)?;
+            for import_name in inner {
+                let mut parts: Pairs<Rule> = import_name.into_inner();
+                let item: &str = parts.next().unwrap().as_str();
+                let value: Value = module.globals.get(item).ok_or_else(|| {
+                    miette::miette!(
+                        "ImportError: Cannot import name '{}' from '{}'.",
+                        item,
+                        name
+                    )
+                })?;
+                let variable: &str = parts.next().map_or(item, |alias| alias.as_str());
+                env.define(variable.to_string(), value);
+            }
+        }
+        Rule::enum_definition => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let name: &'static str = inner.next().unwrap().as_str();
+            let mut variants: Vec<(&'static str, Value)> = Vec::new();
+            // Like Python's auto(), values count up from 1, or from the last number given
+            let mut next: i64 = 1;
+            for variant in inner {
+                let mut parts: Pairs<Rule> = variant.into_inner();
+                let variant_name: &'static str = parts.next().unwrap().as_str();
+                if variants
+                    .iter()
+                    .any(|(existing, _)| *existing == variant_name)
+                {
+                    return Err(miette::miette!(
+                        "Error: Duplicate variant '{}' in enum '{}'.",
+                        variant_name,
+                        name
+                    ));
+                }
+                let value: Value = match parts.next() {
+                    Some(expr) => evaluate_expression(expr, env, stdlib)?,
+                    None => Value::Number(next),
+                };
+                if let Value::Number(n) = value {
+                    next = n + 1;
                 }
+                variants.push((variant_name, value));
             }
+            let enum_type = Enum { name, variants };
+            env.define(name.to_string(), Value::Enum(Rc::new(enum_type)));
+        }
+        Rule::match_statement => {
+            return execute_match_statement(pair, env, stdlib);
+        }
+        Rule::function_definition => {
+            let mut inner: Pairs<Rule> = pair.into_inner();
+            let name: String = inner.next().unwrap().as_str().to_string();
+            let function: Value = make_function(name.clone(), inner, env, stdlib)?;
+            env.define(name, function);
+        }
+        Rule::return_statement => {
+            let value: Value = match pair.into_inner().next() {
+                Some(return_value) => {
+                    let expr: Pair<Rule> = return_value.into_inner().next().unwrap();
+                    evaluate_expression(expr, env, stdlib)?
+                }
+                None => Value::Undefined,
+            };
+            return Ok(Flow::Return(value));
+        }
+        Rule::expression_statement => {
+            evaluate_expression(pair.into_inner().next().unwrap(), env, stdlib)?;
         }
         _ => {}
     }
-    Ok(())
+    Ok(Flow::Next)
+}
+
+/// Runs `f` on the variable or instance field a statement changes in place, such as scores in
+/// scores["kai"] = 1 or self.stock in self.stock["tea"] = 3. Gives None if there is no such
+/// variable.
+fn with_target<R>(
+    target: Pair<'static, Rule>,
+    env: &Env,
+    f: impl FnOnce(&mut Value) -> R,
+) -> miette::Result<Option<R>> {
+    if target.as_rule() == Rule::IDENTIFIER {
+        return Ok(env.with_variable(target.as_str(), f));
+    }
+    let (owner, name) = attribute_owner(target, env)?;
+    if let Value::Instance(instance) = &owner {
+        if let Some(field) = instance.fields.borrow_mut().get_mut(name) {
+            return Ok(Some(f(field)));
+        }
+    }
+    Err(no_attribute(&owner, name))
+}
+
+/// Works out an attribute target up to its last name: for shop.stock.count, the value of
+/// shop.stock and "count"
+fn attribute_owner(
+    target: Pair<'static, Rule>,
+    env: &Env,
+) -> miette::Result<(Value, &'static str)> {
+    let mut names = target.into_inner().map(|name| name.as_str());
+    let first: &str = names.next().unwrap();
+    let mut owner: Value = match env.get(first) {
+        Some(value) => value,
+        None => {
+            return Err(miette::miette!(
+                "Error: Variable '{}' is not defined.",
+                first
+            ))
+        }
+    };
+    let mut name: &'static str = names.next().unwrap();
+    for next in names {
+        owner = get_attribute(&owner, name)?;
+        name = next;
+    }
+    Ok((owner, name))
+}
+
+/// Binds a value to an assignment target. A name gets the whole value; a target list such as
+/// `a, (b, c), *rest` unpacks it item by item. `bind` stores each name, e.g. in a scope.
+pub fn bind_target(
+    target: Pair<'static, Rule>,
+    value: Value,
+    bind: &mut dyn FnMut(&str, Value) -> miette::Result<()>,
+) -> miette::Result<()> {
+    if target.as_rule() == Rule::IDENTIFIER {
+        return bind(target.as_str(), value);
+    }
+    let targets: Vec<Pair<Rule>> = target.into_inner().collect();
+    let starred: Vec<usize> = (0..targets.len())
+        .filter(|&i| targets[i].as_rule() == Rule::starred_target)
+        .collect();
+    if starred.len() > 1 {
+        return Err(miette::miette!(
+            "Error: Multiple starred names in assignment."
+        ));
+    }
+    let mut items: Vec<Value> = match value {
+        Value::Array(_) | Value::Tuple(_) | Value::String(_) | Value::Dict(_) | Value::Set(_) => {
+            iterate(value)?
+        }
+        other => {
+            return Err(miette::miette!(
+                "TypeError: cannot unpack non-iterable {} object.",
+                other.type_name()
+            ))
+        }
+    };
+    match starred.first() {
+        None if items.len() > targets.len() => Err(miette::miette!(
+            "ValueError: too many values to unpack (expected {}, got {}).",
+            targets.len(),
+            items.len()
+        )),
+        None if items.len() < targets.len() => Err(miette::miette!(
+            "ValueError: not enough values to unpack (expected {}, got {}).",
+            targets.len(),
+            items.len()
+        )),
+        Some(_) if items.len() < targets.len() - 1 => Err(miette::miette!(
+            "ValueError: not enough values to unpack (expected at least {}, got {}).",
+            targets.len() - 1,
+            items.len()
+        )),
+        _ => {
+            // The starred name takes whatever the names after it don't
+            let after: usize = starred.first().map_or(0, |&i| targets.len() - i - 1);
+            let mut tail: Vec<Value> = items.split_off(items.len() - after);
+            let mut items = items.into_iter();
+            for target in targets {
+                if target.as_rule() == Rule::starred_target {
+                    let var_name: &str = target.into_inner().next().unwrap().as_str();
+                    bind(var_name, Value::Array(items.by_ref().collect()))?;
+                } else {
+                    let item: Value = match items.next() {
+                        Some(item) => item,
+                        None => tail.remove(0),
+                    };
+                    bind_target(target, item, bind)?;
+                }
+            }
+            Ok(())
+        }
+    }
 }
 
-fn is_truthy(value: Value) -> bool {
+/// Defines the names of an assignment target in `scope`, as var and for loops do
+pub fn define_target(target: Pair<'static, Rule>, value: Value, scope: &Env) -> miette::Result<()> {
+    bind_target(target, value, &mut |var_name, value| {
+        scope.define(var_name.to_string(), value);
+        Ok(())
+    })
+}
+
+/// Returns the items a for loop visits: list or tuple items, string characters, dict keys,
+/// set items, an enum's variants or the lines of a file
+pub fn iterate(value: Value) -> miette::Result<Vec<Value>> {
+    match value {
+        Value::Enum(enum_type) => Ok((0..enum_type.variants.len())
+            .map(|index| Value::Variant(Rc::clone(&enum_type), index))
+            .collect()),
+        Value::Array(arr) | Value::Tuple(arr) => Ok(arr),
+        Value::String(s) => Ok(s.chars().map(|c| Value::String(c.to_string())).collect()),
+        Value::Dict(map) => Ok(map.into_keys().collect()),
+        Value::Set(set) => Ok(set.into_iter().collect()),
+        Value::File(file) => read_lines(&file),
+        other => Err(miette::miette!(
+            "Error: '{}' object is not iterable.",
+            other.type_name()
+        )),
+    }
+}
+
+pub fn is_truthy(value: Value) -> bool {
     match value {
         Value::Number(n) => n != 0,
         Value::Float(f) => f != 0.0,
+        Value::Decimal(d) => !d.is_zero(),
         Value::String(ref s) => !s.is_empty(),
         Value::Boolean(b) => b,
-        Value::Array(ref arr) => !arr.is_empty(),
+        Value::Array(ref arr) | Value::Tuple(ref arr) => !arr.is_empty(),
         Value::Dict(ref map) => !map.is_empty(),
-        Value::Undefined => false,
+        Value::Set(ref set) => !set.is_empty(),
+        Value::Duration(duration) => !duration.is_zero(),
+        Value::Function(_)
+        | Value::Class(_)
+        | Value::Instance(_)
+        | Value::Enum(_)
+        | Value::Variant(..)
+        | Value::Module(_)
+        | Value::Date(_)
+        | Value::DateTime(_)
+        | Value::File(_) => true,
+        Value::None | Value::Undefined => false,
     }
 }
 
 fn execute_if_statement(
-    pair: Pair<Rule>,
-    variables: &mut HashMap<String, Value>,
+    pair: Pair<'static, Rule>,
+    env: &Env,
     stdlib: &StdLib,
-) -> miette::Result<()> {
+) -> miette::Result<Flow> {
     let mut inner: Pairs<Rule> = pair.into_inner();
     let condition: Pair<Rule> = inner.next().unwrap();
     let block: Pair<Rule> = inner.next().unwrap();
-    let cond_val = evaluate_expression(condition, variables, stdlib)?;
+    let cond_val = evaluate_expression(condition, env, stdlib)?;
     let condition_met: bool = is_truthy(cond_val);
     if condition_met {
-        let mut local_vars: HashMap<String, Value> = variables.clone();
-        execute_block(block, &mut local_vars, stdlib)?;
-        for (k, v) in local_vars.iter() {
-            if variables.contains_key(k) {
-                variables.insert(k.clone(), v.clone());
-            }
-        }
-        return Ok(());
+        return execute_block(block, env, stdlib);
     } else {
         for elif_or_else in inner {
             match elif_or_else.as_rule() {
@@ -136,50 +977,76 @@ fn execute_if_statement(
                     let mut elif_inner: Pairs<Rule> = elif_or_else.into_inner();
                     let elif_condition: Pair<Rule> = elif_inner.next().unwrap();
                     let elif_block: Pair<Rule> = elif_inner.next().unwrap();
-                    let elif_val = evaluate_expression(elif_condition, variables, stdlib)?;
+                    let elif_val = evaluate_expression(elif_condition, env, stdlib)?;
                     let elif_met: bool = is_truthy(elif_val);
                     if elif_met {
-                        let mut local_vars: HashMap<String, Value> = variables.clone();
-                        execute_block(elif_block, &mut local_vars, stdlib)?;
-                        for (k, v) in local_vars.iter() {
-                            if variables.contains_key(k) {
-                                variables.insert(k.clone(), v.clone());
-                            }
-                        }
-                        return Ok(());
+                        return execute_block(elif_block, env, stdlib);
                     }
                 }
                 Rule::else_block => {
                     let else_block: Pair<Rule> = elif_or_else.into_inner().next().unwrap();
-                    let mut local_vars: HashMap<String, Value> = variables.clone();
-                    execute_block(else_block, &mut local_vars, stdlib)?;
-                    for (k, v) in local_vars.iter() {
-                        if variables.contains_key(k) {
-                            variables.insert(k.clone(), v.clone());
-                        }
-                    }
-                    return Ok(());
+                    return execute_block(else_block, env, stdlib);
                 }
                 _ => {}
             }
         }
     }
-    Ok(())
+    Ok(Flow::Next)
 }
 
-fn execute_block(
-    block: Pair<Rule>,
-    variables: &mut HashMap<String, Value>,
+/// Runs the first case whose pattern matches the value and whose guard, if any, is true.
+/// The names the pattern binds only exist in that case's scope.
+fn execute_match_statement(
+    pair: Pair<'static, Rule>,
+    env: &Env,
     stdlib: &StdLib,
-) -> miette::Result<()> {
-    let mut local_vars: HashMap<String, Value> = variables.clone();
-    for statement in block.into_inner() {
-        execute_statement(statement, &mut local_vars, stdlib)?;
+) -> miette::Result<Flow> {
+    let mut inner: Pairs<Rule> = pair.into_inner();
+    let value: Value = evaluate_expression(inner.next().unwrap(), env, stdlib)?;
+    let cases: Vec<Pair<Rule>> = inner.collect();
+    if let Value::Variant(enum_type, _) = &value {
+        check_exhaustive(enum_type, &cases, env, stdlib)?;
     }
-    for (k, v) in local_vars.iter() {
-        if variables.contains_key(k) {
-            variables.insert(k.clone(), v.clone());
+    for case in cases {
+        let mut parts: Pairs<Rule> = case.into_inner();
+        let pattern: Pair<Rule> = parts.next().unwrap();
+        let mut bindings: Bindings = Vec::new();
+        if !match_pattern(pattern, &value, &mut bindings, env, stdlib)? {
+            continue;
         }
+        let scope: Env = Environment::child(env);
+        for (name, bound) in bindings {
+            scope.define(name, bound);
+        }
+        let mut body: Pair<Rule> = parts.next().unwrap();
+        if body.as_rule() == Rule::match_guard {
+            let guard: Pair<Rule> = body.into_inner().next().unwrap();
+            if !is_truthy(evaluate_expression(guard, &scope, stdlib)?) {
+                continue;
+            }
+            body = parts.next().unwrap();
+        }
+        return execute_statements(body.into_inner(), &scope, stdlib);
     }
-    Ok(())
+    Ok(Flow::Next)
+}
+
+/// Runs a block in a new scope, so variables declared inside it end with it
+fn execute_block(block: Pair<'static, Rule>, env: &Env, stdlib: &StdLib) -> miette::Result<Flow> {
+    let scope: Env = Environment::child(env);
+    execute_statements(block.into_inner(), &scope, stdlib)
+}
+
+/// Runs statements in order until one of them returns
+fn execute_statements(
+    statements: Pairs<'static, Rule>,
+    env: &Env,
+    stdlib: &StdLib,
+) -> miette::Result<Flow> {
+    for statement in statements {
+        if let Flow::Return(value) = execute_statement(statement, env, stdlib)? {
+            return Ok(Flow::Return(value));
+        }
+    }
+    Ok(Flow::Next)
 }
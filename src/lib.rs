@@ -1,6 +1,8 @@
+pub mod environment;
 pub mod eval;
 pub mod interpreter;
 pub mod parser;
+pub mod preprocess;
 pub mod stdlib;
 pub mod value;
 pub use stdlib::StdLib;
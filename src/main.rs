@@ -1,15 +1,21 @@
+mod environment;
 mod eval;
 mod interpreter;
 mod parser;
 mod preprocess;
 mod stdlib;
 mod value;
+use crate::eval::std::std_time::FakeClock;
 use crate::interpreter::run_program;
 use crate::parser::{Rule, TungParser};
 use crate::preprocess::preprocess_code;
+use crate::stdlib::StdLib;
 use ::std::ffi;
 use ::std::fs;
 use ::std::path;
+use ::std::rc::Rc;
+use ::std::thread;
+use chrono::NaiveDateTime;
 use clap::Parser;
 use pest::Parser as PestParserTrait;
 
@@ -19,9 +25,58 @@ pub struct Args {
     /// Path to the TungLang source file
     #[arg(short, long)]
     pub file: String,
+
+    /// Make input() return a number when the line looks like one, as older versions did
+    #[arg(long)]
+    pub legacy_input: bool,
+
+    /// Directory to look for imported modules in, after the importing file's own directory
+    /// (can be given more than once)
+    #[arg(long, value_name = "DIR")]
+    pub module_path: Vec<path::PathBuf>,
+
+    /// Seed for the random module, so that a run can be repeated exactly
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Start the clock at this time (e.g. "2025-06-01 09:00:00") and make sleeping instant, so
+    /// the time module gives the same times on every run
+    #[arg(long, value_name = "START", value_parser = parse_fake_time)]
+    pub fake_time: Option<NaiveDateTime>,
+
+    /// Only let open() use files inside this directory; paths are taken from there
+    #[arg(long, value_name = "DIR")]
+    pub sandbox: Option<path::PathBuf>,
 }
 
+fn parse_fake_time(text: &str) -> Result<NaiveDateTime, String> {
+    [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+    .ok_or_else(|| "expected a time like 2025-06-01 09:00:00".to_string())
+}
+
+// TungLang calls nest several Rust frames deep, so the main thread's stack is too small
+// for recursive programs
+const INTERPRETER_STACK_SIZE: usize = 512 * 1024 * 1024;
+
 fn main() -> miette::Result<()> {
+    let interpreter = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(run)
+        .expect("failed to start the interpreter thread");
+    match interpreter.join() {
+        Ok(result) => result,
+        Err(panic) => ::std::panic::resume_unwind(panic),
+    }
+}
+
+fn run() -> miette::Result<()> {
     let args: Args = Args::parse();
 
     let path: &path::Path = path::Path::new(&args.file);
@@ -41,16 +96,50 @@ fn main() -> miette::Result<()> {
         }
     };
 
-    let program = preprocess_code(&program);
+    // The source is kept for the whole run so that function bodies can hold on to their parse tree
+    let program: &'static str = Box::leak(preprocess_code(&program).into_boxed_str());
 
-    let parsed: pest::iterators::Pairs<Rule> = match TungParser::parse(Rule::program, &program) {
+    let parsed: pest::iterators::Pairs<Rule> = match TungParser::parse(Rule::program, program) {
         Ok(mut pairs) => pairs.next().unwrap().into_inner(),
         Err(e) => {
             return Err(miette::miette!("Error parsing program: {}", e));
         }
     };
 
-    run_program(parsed)?;
+    let mut stdlib: StdLib = StdLib::new();
+    if args.legacy_input {
+        stdlib.use_legacy_input();
+    }
+
+    if let Some(seed) = args.seed {
+        stdlib.seed_random(seed);
+    }
+    if let Some(start) = args.fake_time {
+        stdlib.set_clock(Rc::new(FakeClock::new(start)));
+    }
+    if let Some(dir) = &args.sandbox {
+        match fs::canonicalize(dir) {
+            Ok(root) if root.is_dir() => stdlib.set_file_root(root),
+            _ => {
+                return Err(miette::miette!(
+                    "Error: Sandbox directory {} not found.",
+                    dir.display()
+                ))
+            }
+        }
+    }
+    for dir in args.module_path {
+        stdlib.add_module_path(dir);
+    }
+
+    // Errors from a module's own source already carry it
+    run_program(parsed, path, &stdlib).map_err(|e| {
+        if e.source_code().is_some() {
+            e
+        } else {
+            e.with_source_code(miette::NamedSource::new(&args.file, program))
+        }
+    })?;
 
     Ok(())
 }
@@ -4,7 +4,6 @@ use regex::Regex;
 /// Automatically adds `\b` and `\s*` to either side of the phrase for easier expansion.
 pub fn preprocess_code(code: &str) -> String {
     let replacements = vec![
-        ("")
         ("tung(", "print("),
         ("sahur(", "input("),
         ("tripi(", "int("),
@@ -14,6 +13,7 @@ pub fn preprocess_code(code: &str) -> String {
         ("saturnita", "else"),
         ("bombadillo", "while"),
         ("tralala", "for"),
+        ("patapim", "match"),
         // Add more (phrase, replacement) pairs here as needed
     ];
     let mut result = code.to_string();
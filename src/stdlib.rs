@@ -1,70 +1,503 @@
 // TungLang standard library setup and function dispatch
+use crate::environment::{Env, Environment};
 use crate::eval::std::std_abs::std_abs;
 use crate::eval::std::std_cast;
-use crate::eval::std::std_input::std_input;
+use crate::eval::std::std_dict;
+use crate::eval::std::std_file;
+use crate::eval::std::std_format;
+use crate::eval::std::std_functional;
+use crate::eval::std::std_input::{std_input, std_input_auto_typed};
+use crate::eval::std::std_json;
 use crate::eval::std::std_len::std_len;
 use crate::eval::std::std_list;
 use crate::eval::std::std_math;
-use crate::eval::std::std_print::std_print;
+use crate::eval::std::std_print::std_print_keywords;
+use crate::eval::std::std_random;
 use crate::eval::std::std_range;
-use crate::value::{BuiltinFn, Value};
+use crate::eval::std::std_set;
+use crate::eval::std::std_string;
+use crate::eval::std::std_time::{self, Clock, SystemClock};
+use crate::value::{BuiltinFn, Function, Keywords, Module, NativeFn, Value};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// A builtin function and the way it takes its arguments
+#[derive(Clone, Copy)]
+pub enum Builtin {
+    /// Positional arguments only
+    Positional(BuiltinFn),
+    /// Positional arguments that can also be passed by keyword, named in order
+    Named(BuiltinFn, &'static [&'static str]),
+    /// Positional arguments plus the listed keyword-only arguments, or any keyword arguments
+    /// if the list has "**"
+    Native(NativeFn, &'static [&'static str]),
+}
+
+impl Builtin {
+    /// Calls the builtin, checking the keyword arguments against the ones it accepts
+    pub fn call(
+        &self,
+        name: &str,
+        mut args: Vec<Value>,
+        keywords: Keywords,
+        stdlib: &StdLib,
+    ) -> miette::Result<Value> {
+        match *self {
+            Builtin::Positional(func) => match keywords.keys().next() {
+                Some(keyword) => Err(unexpected_keyword(name, keyword)),
+                None => Ok(func(&args)),
+            },
+            Builtin::Named(func, params) => {
+                let given = args.len();
+                for (keyword, value) in keywords {
+                    let position = match params.iter().position(|param| *param == keyword) {
+                        Some(position) => position,
+                        None => return Err(unexpected_keyword(name, &keyword)),
+                    };
+                    if position < given {
+                        return Err(miette::miette!(
+                            "Error: {}() got multiple values for argument '{}'.",
+                            name,
+                            keyword
+                        ));
+                    }
+                    // Parameters skipped over are left Undefined, which builtins treat as missing
+                    if args.len() <= position {
+                        args.resize(position + 1, Value::Undefined);
+                    }
+                    args[position] = value;
+                }
+                Ok(func(&args))
+            }
+            Builtin::Native(func, accepted) => {
+                let any_keyword: bool = accepted.contains(&"**");
+                if let Some(keyword) = keywords
+                    .keys()
+                    .find(|k| !any_keyword && !accepted.contains(&k.as_str()))
+                {
+                    return Err(unexpected_keyword(name, keyword));
+                }
+                func(&args, &keywords, stdlib)
+            }
+        }
+    }
+}
+
+/// The functions and constants of a module that comes with TungLang, such as math
+pub type NativeModule = Vec<(&'static str, Value)>;
+
+/// A native module's function, for listing in a NativeModule
+pub fn native(name: &'static str, builtin: Builtin) -> (&'static str, Value) {
+    (name, Value::Function(Function::Builtin(name, builtin)))
+}
+
+fn unexpected_keyword(name: &str, keyword: &str) -> miette::Report {
+    miette::miette!(
+        "Error: {}() got an unexpected keyword argument '{}'.",
+        name,
+        keyword
+    )
+}
 
 pub struct StdLib {
-    functions: HashMap<&'static str, BuiltinFn>,
+    functions: HashMap<&'static str, Builtin>,
+    // Methods keyed by receiver type name, then method name; the receiver is passed as the first argument
+    methods: HashMap<&'static str, HashMap<&'static str, Builtin>>,
+    // How many user function calls are currently running
+    call_depth: Cell<usize>,
+    // The random module's generator state
+    random_state: Cell<u64>,
+    // Where the time module gets the time from
+    clock: Rc<dyn Clock>,
+    // The only directory open() may use, if the program runs with --sandbox
+    file_root: Option<PathBuf>,
+    // Modules that come with TungLang, which import finds before any .tung file
+    native_modules: HashMap<&'static str, Rc<Module>>,
+    // Directories searched for imported modules after the importing file's own directory
+    module_path: Vec<PathBuf>,
+    // Modules loaded so far, by file, so each one only runs once
+    modules: RefCell<HashMap<PathBuf, Rc<Module>>>,
+    // Files whose import is still running, outermost first, to catch import cycles
+    importing: RefCell<Vec<PathBuf>>,
+    // The file of the program or module whose code is running
+    running_file: RefCell<String>,
 }
 
 impl StdLib {
     pub fn new() -> Self {
-        let mut functions: HashMap<&'static str, BuiltinFn> = HashMap::new();
+        let mut functions: HashMap<&'static str, Builtin> = HashMap::new();
+        let mut methods: HashMap<&'static str, HashMap<&'static str, Builtin>> = HashMap::new();
 
         // Basic functions
-        functions.insert("input", |args: &[Value]| {
-            std_input(args.get(0).unwrap_or(&Value::String(String::new())))
-        });
-        functions.insert("print", |args: &[Value]| {
-            std_print(args.get(0).unwrap_or(&Value::String(String::new())));
-            Value::Number(0)
-        });
-        functions.insert("abs", |args: &[Value]| {
-            std_abs(args.get(0).unwrap_or(&Value::Number(0)))
-        });
-        functions.insert("len", |args: &[Value]| {
-            std_len(args.get(0).unwrap_or(&Value::String(String::new())))
-        });
-        functions.insert("range", std_range::std_range);
+        functions.insert(
+            "input",
+            Builtin::Positional(|args: &[Value]| {
+                std_input(args.first().unwrap_or(&Value::String(String::new())))
+            }),
+        );
+        functions.insert(
+            "print",
+            Builtin::Native(std_print_keywords, &["sep", "end"]),
+        );
+        functions.insert(
+            "abs",
+            Builtin::Positional(|args: &[Value]| {
+                std_abs(args.first().unwrap_or(&Value::Number(0)))
+            }),
+        );
+        functions.insert(
+            "len",
+            Builtin::Positional(|args: &[Value]| {
+                std_len(args.first().unwrap_or(&Value::String(String::new())))
+            }),
+        );
+        functions.insert("range", Builtin::Positional(std_range::std_range));
 
         // Type conversion functions (like Python)
-        functions.insert("int", |args: &[Value]| {
-            std_cast::std_int(args.get(0).unwrap_or(&Value::Number(0)))
-        });
-        functions.insert("str", |args: &[Value]| {
-            std_cast::std_str(args.get(0).unwrap_or(&Value::String(String::new())))
-        });
-        functions.insert("float", |args: &[Value]| {
-            std_cast::std_float(args.get(0).unwrap_or(&Value::Number(0)))
-        });
-        functions.insert("bool", |args: &[Value]| {
-            std_cast::std_bool(args.get(0).unwrap_or(&Value::Number(0)))
-        });
+        functions.insert(
+            "int",
+            Builtin::Positional(|args: &[Value]| {
+                std_cast::std_int(args.first().unwrap_or(&Value::Number(0)))
+            }),
+        );
+        functions.insert(
+            "str",
+            Builtin::Positional(|args: &[Value]| {
+                std_cast::std_str(args.first().unwrap_or(&Value::String(String::new())))
+            }),
+        );
+        functions.insert(
+            "repr",
+            Builtin::Positional(|args: &[Value]| {
+                std_cast::std_repr(args.first().unwrap_or(&Value::Undefined))
+            }),
+        );
+        functions.insert(
+            "float",
+            Builtin::Positional(|args: &[Value]| {
+                std_cast::std_float(args.first().unwrap_or(&Value::Number(0)))
+            }),
+        );
+        functions.insert(
+            "bool",
+            Builtin::Positional(|args: &[Value]| {
+                std_cast::std_bool(args.first().unwrap_or(&Value::Number(0)))
+            }),
+        );
+        functions.insert(
+            "list",
+            Builtin::Positional(|args: &[Value]| {
+                std_cast::std_list(args.first().unwrap_or(&Value::Array(vec![])))
+            }),
+        );
+        functions.insert(
+            "tuple",
+            Builtin::Positional(|args: &[Value]| {
+                std_cast::std_tuple(args.first().unwrap_or(&Value::Tuple(vec![])))
+            }),
+        );
+        functions.insert("isinstance", Builtin::Positional(std_cast::std_isinstance));
+        functions.insert(
+            "decimal",
+            Builtin::Positional(|args: &[Value]| {
+                std_cast::std_decimal(args.first().unwrap_or(&Value::Number(0)))
+            }),
+        );
 
         // Math functions (like Python)
-        functions.insert("min", std_math::std_min);
-        functions.insert("max", std_math::std_max);
-        functions.insert("sum", std_math::std_sum);
-        functions.insert("round", std_math::std_round);
+        functions.insert("min", Builtin::Positional(std_math::std_min));
+        functions.insert("max", Builtin::Positional(std_math::std_max));
+        functions.insert("sum", Builtin::Positional(std_math::std_sum));
+        functions.insert(
+            "round",
+            Builtin::Named(std_math::std_round, &["number", "ndigits", "mode"]),
+        );
 
         // List functions (like Python)
-        functions.insert("append", std_list::std_append);
-        functions.insert("insert", std_list::std_insert);
-        functions.insert("pop", std_list::std_pop);
-        functions.insert("index", std_list::std_index);
-        functions.insert("sort", std_list::std_sort);
+        functions.insert("append", Builtin::Positional(std_list::std_append));
+        functions.insert("insert", Builtin::Positional(std_list::std_insert));
+        functions.insert("pop", Builtin::Positional(std_list::std_pop));
+        functions.insert("index", Builtin::Positional(std_list::std_index));
+
+        // Functional tools (like Python); sort and sorted take key=f and reverse=true
+        functions.insert("map", Builtin::Native(std_functional::std_map, &[]));
+        functions.insert("filter", Builtin::Native(std_functional::std_filter, &[]));
+        functions.insert("reduce", Builtin::Native(std_functional::std_reduce, &[]));
+        functions.insert(
+            "sorted",
+            Builtin::Native(std_functional::std_sorted, &["key", "reverse"]),
+        );
+        functions.insert(
+            "sort",
+            Builtin::Native(std_functional::std_sort, &["key", "reverse"]),
+        );
+        functions.insert("any", Builtin::Positional(std_functional::std_any));
+        functions.insert("all", Builtin::Positional(std_functional::std_all));
+        functions.insert(
+            "enumerate",
+            Builtin::Named(std_functional::std_enumerate, &["iterable", "start"]),
+        );
+        functions.insert("zip", Builtin::Positional(std_functional::std_zip));
+        functions.insert(
+            "reversed",
+            Builtin::Positional(std_functional::std_reversed),
+        );
+
+        // Dict functions (like Python), also callable as methods: scores.keys()
+        let dict_functions: [(&'static str, Builtin); 6] = [
+            ("keys", Builtin::Positional(std_dict::std_keys)),
+            ("values", Builtin::Positional(std_dict::std_values)),
+            ("items", Builtin::Positional(std_dict::std_items)),
+            ("get", Builtin::Positional(std_dict::std_get)),
+            ("update", Builtin::Positional(std_dict::std_update)),
+            ("setdefault", Builtin::Positional(std_dict::std_setdefault)),
+        ];
+        let dict_methods = methods.entry("dict").or_default();
+        for (name, function) in dict_functions {
+            functions.insert(name, function);
+            dict_methods.insert(name, function);
+        }
+
+        // Set functions (like Python), also callable as methods: choices.add("c")
+        functions.insert("set", Builtin::Native(std_set::std_set, &[]));
+        let set_functions: [(&'static str, Builtin); 3] = [
+            ("add", Builtin::Native(std_set::std_add, &[])),
+            ("remove", Builtin::Native(std_set::std_remove, &[])),
+            ("discard", Builtin::Native(std_set::std_discard, &[])),
+        ];
+        let set_methods = methods.entry("set").or_default();
+        for (name, function) in set_functions {
+            functions.insert(name, function);
+            set_methods.insert(name, function);
+        }
+
+        // String functions (like Python), also callable as methods: "abc".upper()
+        let string_functions: [(&'static str, Builtin); 15] = [
+            ("upper", Builtin::Positional(std_string::std_upper)),
+            ("lower", Builtin::Positional(std_string::std_lower)),
+            ("strip", Builtin::Positional(std_string::std_strip)),
+            (
+                "split",
+                Builtin::Named(std_string::std_split, &["string", "sep", "maxsplit"]),
+            ),
+            ("join", Builtin::Positional(std_string::std_join)),
+            ("replace", Builtin::Positional(std_string::std_replace)),
+            (
+                "startswith",
+                Builtin::Positional(std_string::std_startswith),
+            ),
+            ("endswith", Builtin::Positional(std_string::std_endswith)),
+            ("find", Builtin::Positional(std_string::std_find)),
+            ("count", Builtin::Positional(std_string::std_count)),
+            ("isdigit", Builtin::Positional(std_string::std_isdigit)),
+            ("isalpha", Builtin::Positional(std_string::std_isalpha)),
+            ("title", Builtin::Positional(std_string::std_title)),
+            ("center", Builtin::Positional(std_string::std_center)),
+            ("zfill", Builtin::Positional(std_string::std_zfill)),
+        ];
+        let str_methods = methods.entry("str").or_default();
+        for (name, function) in string_functions {
+            functions.insert(name, function);
+            str_methods.insert(name, function);
+        }
+        functions.insert("chr", Builtin::Positional(std_string::std_chr));
+        functions.insert("ord", Builtin::Positional(std_string::std_ord));
+
+        // String formatting functions (like Python)
+        functions.insert("format", Builtin::Native(std_format::std_format, &[]));
+        str_methods.insert(
+            "format",
+            Builtin::Native(std_format::std_str_format, &["**"]),
+        );
 
-        Self { functions }
+        // Files (like Python): open(path, mode) gives a file with read, write and close methods
+        functions.insert("open", Builtin::Native(std_file::std_open, &["mode"]));
+        methods
+            .entry("file")
+            .or_default()
+            .extend(std_file::file_methods());
+
+        // Date, datetime and duration methods (made with the time module): d.weekday()
+        for type_name in ["date", "datetime"] {
+            methods
+                .entry(type_name)
+                .or_default()
+                .extend(std_time::date_methods());
+        }
+        methods
+            .entry("datetime")
+            .or_default()
+            .insert("date", Builtin::Native(std_time::std_date_part, &[]));
+        methods.entry("duration").or_default().insert(
+            "total_seconds",
+            Builtin::Native(std_time::std_total_seconds, &[]),
+        );
+
+        // Native modules: functions that would crowd the global names live in a module instead,
+        // like Python's, and are used as math.pi after import math
+        let mut native_modules: HashMap<&'static str, Rc<Module>> = HashMap::new();
+        let module_members: [(&'static str, NativeModule); 5] = [
+            ("json", std_json::json_module()),
+            ("math", std_math::math_module()),
+            ("random", std_random::random_module()),
+            ("string", std_string::string_module()),
+            ("time", std_time::time_module()),
+        ];
+        for (name, members) in module_members {
+            let globals: Env = Environment::global();
+            for (member, value) in members {
+                globals.define(member.to_string(), value);
+            }
+            let module = Module {
+                name: name.to_string(),
+                globals,
+            };
+            native_modules.insert(name, Rc::new(module));
+        }
+
+        Self {
+            functions,
+            methods,
+            native_modules,
+            call_depth: Cell::new(0),
+            random_state: Cell::new(std_random::time_seed()),
+            clock: Rc::new(SystemClock::new()),
+            file_root: None,
+            module_path: Vec::new(),
+            modules: RefCell::new(HashMap::new()),
+            importing: RefCell::new(Vec::new()),
+            running_file: RefCell::new(String::new()),
+        }
+    }
+
+    /// Makes input() guess the type of what was typed, as older versions did
+    pub fn use_legacy_input(&mut self) {
+        self.functions.insert(
+            "input",
+            Builtin::Positional(|args: &[Value]| {
+                std_input_auto_typed(args.first().unwrap_or(&Value::String(String::new())))
+            }),
+        );
     }
 
-    pub fn get(&self, name: &str) -> Option<&BuiltinFn> {
+    pub fn get(&self, name: &str) -> Option<&Builtin> {
         self.functions.get(name)
     }
+
+    pub fn get_method(&self, type_name: &str, name: &str) -> Option<&Builtin> {
+        self.methods.get(type_name)?.get(name)
+    }
+
+    /// Looks up a builtin function as a value, e.g. for var measure = len
+    pub fn lookup(&self, name: &str) -> Option<Function> {
+        let (name, builtin) = self.functions.get_key_value(name)?;
+        Some(Function::Builtin(name, *builtin))
+    }
+
+    /// Tracks entering (+1) or leaving (-1) a user function call and returns the new depth
+    pub fn adjust_call_depth(&self, change: isize) -> usize {
+        let depth = self.call_depth.get().saturating_add_signed(change);
+        self.call_depth.set(depth);
+        depth
+    }
+
+    /// Restarts the random module's numbers from `seed`, as random.seed(n) and --seed do
+    pub fn seed_random(&self, seed: u64) {
+        self.random_state.set(seed);
+    }
+
+    /// The random module's next 64 random bits (SplitMix64, which is small and the same
+    /// everywhere, so a seed gives the same numbers on every machine)
+    pub fn next_random(&self) -> u64 {
+        let state = self.random_state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.random_state.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Replaces the clock the time module uses, e.g. with a FakeClock so a test gets the same
+    /// times on every run
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Keeps open() inside `root`: paths are taken from there and can't lead out of it. `root`
+    /// should already be canonical, so that paths inside it can be checked against it.
+    pub fn set_file_root(&mut self, root: PathBuf) {
+        self.file_root = Some(root);
+    }
+
+    pub fn file_root(&self) -> Option<&Path> {
+        self.file_root.as_deref()
+    }
+
+    /// Returns the module that comes with TungLang called `name`, if there is one
+    pub fn native_module(&self, name: &str) -> Option<Rc<Module>> {
+        self.native_modules.get(name).cloned()
+    }
+
+    /// Adds a directory to search for imported modules
+    pub fn add_module_path(&mut self, dir: PathBuf) {
+        self.module_path.push(dir);
+    }
+
+    pub fn module_path(&self) -> &[PathBuf] {
+        &self.module_path
+    }
+
+    /// Returns the module already loaded from `file`, if any
+    pub fn loaded_module(&self, file: &Path) -> Option<Rc<Module>> {
+        self.modules.borrow().get(file).cloned()
+    }
+
+    pub fn add_loaded_module(&self, file: PathBuf, module: Rc<Module>) {
+        self.modules.borrow_mut().insert(file, module);
+    }
+
+    /// Marks `file` as being imported. If it already is, the import is a cycle and the error
+    /// lists the chain of files that led back to it.
+    pub fn start_import(&self, file: &Path) -> miette::Result<()> {
+        let mut importing = self.importing.borrow_mut();
+        if let Some(start) = importing.iter().position(|f| f == file) {
+            let chain: Vec<String> = importing[start..]
+                .iter()
+                .chain([&file.to_path_buf()])
+                .map(|f| f.display().to_string())
+                .collect();
+            return Err(miette::miette!(
+                "ImportError: Cyclic import: {}.",
+                chain.join(" -> ")
+            ));
+        }
+        importing.push(file.to_path_buf());
+        Ok(())
+    }
+
+    pub fn finish_import(&self) {
+        self.importing.borrow_mut().pop();
+    }
+
+    pub fn running_file(&self) -> String {
+        self.running_file.borrow().clone()
+    }
+
+    /// Records that code from `file` is running and returns the file that was before, to be
+    /// put back when it finishes
+    pub fn set_running_file(&self, file: String) -> String {
+        self.running_file.replace(file)
+    }
+}
+
+impl Default for StdLib {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -1,57 +1,619 @@
-#[derive(Debug, Clone, PartialEq)]
+use crate::environment::Env;
+use crate::interpreter::UserFunction;
+use crate::stdlib::{Builtin, StdLib};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeDelta, Timelike};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::cell::RefCell;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
 pub enum Value {
     Number(i64),
     Float(f64),
     String(String),
     Boolean(bool),
+    Decimal(Decimal),
     Array(Vec<Value>),
-    Dict(std::collections::HashMap<String, Value>),
+    Tuple(Vec<Value>),
+    Dict(indexmap::IndexMap<Value, Value>),
+    Set(indexmap::IndexSet<Value>),
+    Function(Function),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    Enum(Rc<Enum>),
+    /// One of an enum's variants, by its position in the enum
+    Variant(Rc<Enum>, usize),
+    Module(Rc<Module>),
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+    Duration(TimeDelta),
+    File(Rc<File>),
+    /// Python's None, which is what JSON's null reads as
+    None,
     Undefined, // Added to represent undefined values
 }
 
 pub type BuiltinFn = fn(&[Value]) -> Value;
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Number(pub i64);
+/// Keyword arguments of a call, in the order they were written
+pub type Keywords = indexmap::IndexMap<String, Value>;
+
+/// A builtin that also receives keyword arguments and the standard library, so it can take
+/// options such as print's sep and end, call functions it is given and report errors
+pub type NativeFn = fn(&[Value], &Keywords, &StdLib) -> miette::Result<Value>;
+
+/// A function as a value: it can be stored in a variable, passed to another function and called
+#[derive(Clone)]
+pub enum Function {
+    /// A builtin such as len, with its name
+    Builtin(&'static str, Builtin),
+    /// A function made with def, lambda or fn
+    User(Rc<UserFunction>),
+    /// A method taken from an instance, e.g. account.deposit, which passes the instance as self
+    Bound(Rc<Instance>, Rc<UserFunction>),
+}
+
+impl Function {
+    pub fn name(&self) -> &str {
+        match self {
+            Function::Builtin(name, _) => name,
+            Function::User(function) | Function::Bound(_, function) => &function.name,
+        }
+    }
+}
+
+/// A class made with the class statement: its methods and the class it inherits from
+pub struct Class {
+    pub name: &'static str,
+    pub parent: Option<Rc<Class>>,
+    pub methods: indexmap::IndexMap<String, Rc<UserFunction>>,
+}
+
+impl Class {
+    /// Looks a method up in this class, then in the classes it inherits from
+    pub fn find_method(&self, name: &str) -> Option<Rc<UserFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.parent.as_ref()?.find_method(name),
+        }
+    }
+
+    /// Whether this class is `other` or inherits from it
+    pub fn is_subclass_of(&self, other: &Class) -> bool {
+        std::ptr::eq(self, other)
+            || self
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.is_subclass_of(other))
+    }
+}
+
+/// An object made by calling a class. Instances are shared rather than copied, so a change
+/// made through self in a method is seen by everyone holding the instance.
+pub struct Instance {
+    pub class: Rc<Class>,
+    /// The instance's fields, in the order they were first set
+    pub fields: RefCell<indexmap::IndexMap<String, Value>>,
+}
+
+/// A file made with open(). Reading files are read in whole when opened; writes go straight
+/// to the file, so nothing is lost if the program stops before closing it.
+pub struct File {
+    pub name: String,
+    pub mode: String,
+    /// None once the file has been closed
+    pub handle: RefCell<Option<FileHandle>>,
+}
+
+pub enum FileHandle {
+    /// The file's text and how much of it has been read
+    Read {
+        text: String,
+        position: usize,
+    },
+    Write(std::fs::File),
+}
+
+impl File {
+    pub fn close(&self) {
+        self.handle.borrow_mut().take();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.handle.borrow().is_none()
+    }
+}
+
+impl std::fmt::Debug for File {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "File({})", self.name)
+    }
+}
+
+/// An enum made with the enum statement: its variants' names and values, in order
+pub struct Enum {
+    pub name: &'static str,
+    pub variants: Vec<(&'static str, Value)>,
+}
+
+/// A module loaded by import: its top-level variables live in its own global scope
+pub struct Module {
+    pub name: String,
+    pub globals: Env,
+}
+
+impl std::fmt::Debug for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Module({})", self.name)
+    }
+}
+
+impl std::fmt::Debug for Enum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Enum({})", self.name)
+    }
+}
+
+impl std::fmt::Debug for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Class({})", self.name)
+    }
+}
+
+impl std::fmt::Debug for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Instance({})", self.class.name)
+    }
+}
+
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Function({})", self.name())
+    }
+}
+
+impl Value {
+    /// The Python-style type name used in error messages and method lookup
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "str",
+            Value::Boolean(_) => "bool",
+            Value::Decimal(_) => "decimal",
+            Value::Array(_) => "list",
+            Value::Tuple(_) => "tuple",
+            Value::Dict(_) => "dict",
+            Value::Set(_) => "set",
+            Value::Function(_) => "function",
+            Value::Class(_) | Value::Enum(_) => "type",
+            // Instances are named after their class in error messages, like in Python
+            Value::Instance(instance) => instance.class.name,
+            Value::Variant(enum_type, _) => enum_type.name,
+            Value::Module(_) => "module",
+            Value::Date(_) => "date",
+            Value::DateTime(_) => "datetime",
+            Value::Duration(_) => "duration",
+            Value::File(_) => "file",
+            Value::None => "NoneType",
+            Value::Undefined => "undefined",
+        }
+    }
+
+    /// Whether the value can be used as a dict key. Mutable containers can't, and neither
+    /// can tuples holding one.
+    pub fn is_hashable(&self) -> bool {
+        match self {
+            Value::Array(_) | Value::Dict(_) | Value::Set(_) => false,
+            Value::Tuple(items) => items.iter().all(Value::is_hashable),
+            _ => true,
+        }
+    }
+}
+
+// Equality follows Python: numbers compare by value whatever their type, so 1, 1.0 and
+// decimal("1.00") are all equal and are the same dict key. Booleans are not numbers here.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Decimal(a), Value::Decimal(b)) => a == b,
+            (Value::Number(n), Value::Float(f)) | (Value::Float(f), Value::Number(n)) => {
+                float_to_integer(*f) == Some(*n as i128)
+            }
+            (Value::Number(n), Value::Decimal(d)) | (Value::Decimal(d), Value::Number(n)) => {
+                Decimal::from(*n) == *d
+            }
+            (Value::Float(f), Value::Decimal(d)) | (Value::Decimal(d), Value::Float(f)) => {
+                Decimal::from_f64_retain(*f) == Some(*d)
+            }
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            (Value::Dict(a), Value::Dict(b)) => a == b,
+            (Value::Set(a), Value::Set(b)) => a == b,
+            // Functions are only equal to themselves
+            (
+                Value::Function(Function::Builtin(a, _)),
+                Value::Function(Function::Builtin(b, _)),
+            ) => a == b,
+            (Value::Function(Function::User(a)), Value::Function(Function::User(b))) => {
+                Rc::ptr_eq(a, b)
+            }
+            (
+                Value::Function(Function::Bound(a, a_method)),
+                Value::Function(Function::Bound(b, b_method)),
+            ) => Rc::ptr_eq(a, b) && Rc::ptr_eq(a_method, b_method),
+            // Classes and instances are only equal to themselves too
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            // A variant is only equal to itself, never to its value: Currency.GBP != 1
+            (Value::Enum(a), Value::Enum(b)) => Rc::ptr_eq(a, b),
+            (Value::Variant(a, i), Value::Variant(b, j)) => Rc::ptr_eq(a, b) && i == j,
+            (Value::Module(a), Value::Module(b)) => Rc::ptr_eq(a, b),
+            (Value::Date(a), Value::Date(b)) => a == b,
+            (Value::DateTime(a), Value::DateTime(b)) => a == b,
+            (Value::Duration(a), Value::Duration(b)) => a == b,
+            (Value::File(a), Value::File(b)) => Rc::ptr_eq(a, b),
+            (Value::None, Value::None) => true,
+            (Value::Undefined, Value::Undefined) => true,
+            _ => false,
+        }
+    }
+}
+
+// NaN is the one value that isn't equal to itself; like Python, a NaN key can be
+// stored in a dict but never looked up again
+impl Eq for Value {}
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct StringValue(pub String);
+// Equal numbers must hash alike across int, float and decimal: integral values hash
+// as integers, other floats by their bits, and decimals as the float they equal if any
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Value::Number(n) => hash_integer(*n as i128, state),
+            Value::Float(f) => match float_to_integer(*f) {
+                Some(n) => hash_integer(n, state),
+                None => hash_float(*f, state),
+            },
+            Value::Decimal(d) => {
+                if d.is_integer() {
+                    if let Some(n) = d.to_i128() {
+                        return hash_integer(n, state);
+                    }
+                }
+                match d
+                    .to_f64()
+                    .filter(|f| Decimal::from_f64_retain(*f) == Some(*d))
+                {
+                    Some(f) => hash_float(f, state),
+                    None => {
+                        2u8.hash(state);
+                        d.normalize().hash(state);
+                    }
+                }
+            }
+            Value::String(s) => {
+                3u8.hash(state);
+                s.hash(state);
+            }
+            Value::Boolean(b) => {
+                4u8.hash(state);
+                b.hash(state);
+            }
+            Value::Array(a) => {
+                5u8.hash(state);
+                a.hash(state);
+            }
+            Value::Tuple(t) => {
+                10u8.hash(state);
+                t.hash(state);
+            }
+            Value::Dict(d) => {
+                // Dict equality ignores order, so only the length can be hashed
+                6u8.hash(state);
+                d.len().hash(state);
+            }
+            Value::Set(s) => {
+                // Like dicts, sets are equal whatever their order
+                9u8.hash(state);
+                s.len().hash(state);
+            }
+            Value::None => 20u8.hash(state),
+            Value::Undefined => 7u8.hash(state),
+            Value::Function(function) => {
+                8u8.hash(state);
+                match function {
+                    Function::Builtin(name, _) => name.hash(state),
+                    Function::User(function) => Rc::as_ptr(function).hash(state),
+                    Function::Bound(instance, function) => {
+                        Rc::as_ptr(instance).hash(state);
+                        Rc::as_ptr(function).hash(state);
+                    }
+                }
+            }
+            Value::Class(class) => {
+                11u8.hash(state);
+                Rc::as_ptr(class).hash(state);
+            }
+            Value::Instance(instance) => {
+                12u8.hash(state);
+                Rc::as_ptr(instance).hash(state);
+            }
+            Value::Enum(enum_type) => {
+                13u8.hash(state);
+                Rc::as_ptr(enum_type).hash(state);
+            }
+            Value::Variant(enum_type, index) => {
+                14u8.hash(state);
+                Rc::as_ptr(enum_type).hash(state);
+                index.hash(state);
+            }
+            Value::Module(module) => {
+                15u8.hash(state);
+                Rc::as_ptr(module).hash(state);
+            }
+            Value::Date(date) => {
+                16u8.hash(state);
+                date.hash(state);
+            }
+            Value::DateTime(datetime) => {
+                17u8.hash(state);
+                datetime.hash(state);
+            }
+            Value::Duration(duration) => {
+                18u8.hash(state);
+                duration.hash(state);
+            }
+            Value::File(file) => {
+                19u8.hash(state);
+                Rc::as_ptr(file).hash(state);
+            }
+        }
+    }
+}
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct BooleanValue(pub bool);
+fn float_to_integer(f: f64) -> Option<i128> {
+    if f.is_finite() && f.fract() == 0.0 && f.abs() < 1e38 {
+        Some(f as i128)
+    } else {
+        None
+    }
+}
 
+/// Splits a duration the way Python's timedelta does: whole days (negative for a negative
+/// duration), then the seconds and microseconds left over, which are never negative
+pub fn duration_parts(duration: &TimeDelta) -> (i64, i64, i64) {
+    let micros: i64 = duration.num_microseconds().unwrap_or(i64::MAX);
+    let days: i64 = micros.div_euclid(86_400_000_000);
+    let rest: i64 = micros.rem_euclid(86_400_000_000);
+    (days, rest / 1_000_000, rest % 1_000_000)
+}
+
+fn hash_integer<H: Hasher>(n: i128, state: &mut H) {
+    0u8.hash(state);
+    n.hash(state);
+}
+
+fn hash_float<H: Hasher>(f: f64, state: &mut H) {
+    1u8.hash(state);
+    f.to_bits().hash(state);
+}
+
+impl Value {
+    /// Python-style repr: how the value is written inside a list or dict, with
+    /// strings quoted and escaped, e.g. `['a', 1.0, true]`
+    pub fn repr(&self) -> String {
+        match self {
+            Value::String(s) => repr_string(s),
+            Value::Decimal(d) => format!("decimal('{}')", d),
+            // Dates and durations show the call that would make them, like decimals
+            Value::Date(date) => format!("date({}, {}, {})", date.year(), date.month(), date.day()),
+            Value::DateTime(datetime) => format!(
+                "datetime({}, {}, {}, {}, {}, {})",
+                datetime.year(),
+                datetime.month(),
+                datetime.day(),
+                datetime.hour(),
+                datetime.minute(),
+                datetime.second()
+            ),
+            Value::Duration(duration) => {
+                let (days, seconds, micros) = duration_parts(duration);
+                let parts: Vec<String> = [
+                    ("days", days),
+                    ("seconds", seconds),
+                    ("microseconds", micros),
+                ]
+                .iter()
+                .filter(|(_, amount)| *amount != 0)
+                .map(|(unit, amount)| format!("{}={}", unit, amount))
+                .collect();
+                match parts.is_empty() {
+                    true => "duration(0)".to_string(),
+                    false => format!("duration({})", parts.join(", ")),
+                }
+            }
+            Value::Array(a) => {
+                let items: Vec<String> = a.iter().map(Value::repr).collect();
+                format!("[{}]", items.join(", "))
+            }
+            // A tuple of one needs its comma, or it would read as a value in parentheses
+            Value::Tuple(t) if t.len() == 1 => format!("({},)", t[0].repr()),
+            Value::Tuple(t) => {
+                let items: Vec<String> = t.iter().map(Value::repr).collect();
+                format!("({})", items.join(", "))
+            }
+            Value::Dict(d) => {
+                let items: Vec<String> = d
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k.repr(), v.repr()))
+                    .collect();
+                format!("{{{}}}", items.join(", "))
+            }
+            // {} is an empty dict, so an empty set shows as set() like in Python
+            Value::Set(s) if s.is_empty() => "set()".to_string(),
+            Value::Set(s) => {
+                let items: Vec<String> = s.iter().map(Value::repr).collect();
+                format!("{{{}}}", items.join(", "))
+            }
+            // Instances show their class and fields like a record: Account(owner='Kai', balance=10)
+            Value::Instance(instance) => {
+                let pointer: *const Instance = Rc::as_ptr(instance);
+                let seen = REPRS_IN_PROGRESS.with(|active| {
+                    let mut active = active.borrow_mut();
+                    let seen = active.contains(&pointer);
+                    active.push(pointer);
+                    seen
+                });
+                let fields: Vec<String> = if seen {
+                    vec!["...".to_string()]
+                } else {
+                    instance
+                        .fields
+                        .borrow()
+                        .iter()
+                        .map(|(name, value)| format!("{}={}", name, value.repr()))
+                        .collect()
+                };
+                REPRS_IN_PROGRESS.with(|active| active.borrow_mut().pop());
+                format!("{}({})", instance.class.name, fields.join(", "))
+            }
+            other => other.to_string(),
+        }
+    }
+}
+
+thread_local! {
+    // Instances whose repr is being built, so that one holding itself shows as Name(...)
+    static REPRS_IN_PROGRESS: RefCell<Vec<*const Instance>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Python-style str: what print() and str() show. Strings appear as-is, floats
+/// always have a decimal point or exponent, booleans are `true`/`false`, and
+/// containers show their items' repr.
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Number(n) => write!(f, "{}", n),
-            Value::Float(n) => write!(f, "{}", n),
+            Value::Float(n) => write!(f, "{}", repr_float(*n)),
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
-            Value::Array(a) => {
-                write!(f, "[")?;
-                let mut first = true;
-                for item in a {
-                    if !first {
-                        write!(f, ", ")?;
-                    }
-                    write!(f, "{}", item)?;
-                    first = false;
+            Value::Decimal(d) => write!(f, "{}", d),
+            Value::Array(_)
+            | Value::Tuple(_)
+            | Value::Dict(_)
+            | Value::Set(_)
+            | Value::Instance(_) => write!(f, "{}", self.repr()),
+            Value::Function(Function::Builtin(name, _)) => {
+                write!(f, "<built-in function {}>", name)
+            }
+            Value::Function(Function::User(function)) => write!(f, "<function {}>", function.name),
+            Value::Function(Function::Bound(instance, function)) => write!(
+                f,
+                "<bound method {}.{}>",
+                instance.class.name, function.name
+            ),
+            Value::Class(class) => write!(f, "<class '{}'>", class.name),
+            Value::Enum(enum_type) => write!(f, "<enum '{}'>", enum_type.name),
+            Value::Module(module) => write!(f, "<module '{}'>", module.name),
+            Value::File(file) => write!(f, "<file '{}' mode '{}'>", file.name, file.mode),
+            Value::Date(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+            // Fractions of a second only show when there are some, like Python's datetime
+            Value::DateTime(datetime) => {
+                write!(f, "{}", datetime.format("%Y-%m-%d %H:%M:%S"))?;
+                match datetime.nanosecond() / 1000 {
+                    0 => Ok(()),
+                    micros => write!(f, ".{:06}", micros),
                 }
-                write!(f, "]")
             }
-            Value::Dict(d) => {
-                write!(f, "{{")?;
-                let mut first = true;
-                for (key, val) in d {
-                    if !first {
-                        write!(f, ", ")?;
-                    }
-                    write!(f, "\"{}\": {}", key, val)?;
-                    first = false;
+            // Like Python's timedelta: 1 day, 2:03:04 or -1 day, 23:59:00
+            Value::Duration(duration) => {
+                let (days, seconds, micros) = duration_parts(duration);
+                if days != 0 {
+                    let plural = if days.abs() == 1 { "" } else { "s" };
+                    write!(f, "{} day{}, ", days, plural)?;
+                }
+                write!(
+                    f,
+                    "{}:{:02}:{:02}",
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60
+                )?;
+                match micros {
+                    0 => Ok(()),
+                    micros => write!(f, ".{:06}", micros),
                 }
-                write!(f, "}}")
             }
+            Value::Variant(enum_type, index) => {
+                write!(f, "{}.{}", enum_type.name, enum_type.variants[*index].0)
+            }
+            Value::None => write!(f, "None"),
             Value::Undefined => write!(f, "undefined"),
         }
     }
 }
+
+/// Shortest round-tripping float text, switching to exponent notation outside
+/// 1e-4..1e16 like Python: 1.0, 0.1, 1e+16, 1.5e-05
+pub fn repr_float(f: f64) -> String {
+    if f.is_nan() {
+        return "nan".to_string();
+    }
+    if f.is_infinite() {
+        return if f > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    let scientific = format!("{:e}", f);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    if (-4..16).contains(&exponent) {
+        let fixed = f.to_string();
+        if fixed.contains('.') {
+            fixed
+        } else {
+            fixed + ".0"
+        }
+    } else {
+        format!(
+            "{}e{}{:02}",
+            mantissa,
+            if exponent < 0 { '-' } else { '+' },
+            exponent.abs()
+        )
+    }
+}
+
+// Quotes with ' unless the string contains ' but no ", escaping what needs it
+fn repr_string(s: &str) -> String {
+    let quote = if s.contains('\'') && !s.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push(quote);
+    for c in s.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\r' => result.push_str("\\r"),
+            c if c == quote => {
+                result.push('\\');
+                result.push(c);
+            }
+            c if c.is_control() && (c as u32) < 0x100 => {
+                result.push_str(&format!("\\x{:02x}", c as u32))
+            }
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push(quote);
+    result
+}